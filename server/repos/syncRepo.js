@@ -13,11 +13,24 @@ const DEFAULT_CONFIG = {
   lastSyncedAt: null
 };
 
+/** Aparelho sem push há mais tempo que isso deixa de bloquear o expurgo de lápides. */
+const DEVICE_TTL_MS = 90 * 24 * 60 * 60 * 1000;
+
+const TABELA_POR_ENTIDADE = { transacao: 'transacoes', recorrencia: 'recorrentes' };
+
+function chaveLapide(t) {
+  return `${t.entidade}:${t.id}`;
+}
+
 /**
  * Retorna o snapshot mais recente do usuário.
+ * `cursor` é o número do último push recebido; cada transação gravada leva o do seu push em `seq`.
+ * Como o número vem do servidor, uma edição feita offline e enviada depois ainda entra no
+ * próximo pull, o que não acontece filtrando pelo updatedAt (relógio de quem editou).
  * @param {string} userId
- * @param {string} [since] - Filtrar transacoes/recorrentes com updatedAt >= since (ISO). Se vazio, retorna snapshot completo.
- * @returns { { transacoes, recorrentes, config } } ou estrutura vazia
+ * @param {string} [since] - `cursor` de um GET anterior: só transações recebidas depois dele. Um ISO
+ *   (clientes antigos) filtra por updatedAt >= since. Se vazio, retorna snapshot completo.
+ * @returns { { transacoes, recorrentes, tombstones, config, cursor } } ou estrutura vazia
  */
 export function getSnapshot(userId, since = '') {
   const db = getDb();
@@ -30,7 +43,10 @@ export function getSnapshot(userId, since = '') {
     return {
       transacoes: [],
       recorrentes: [],
-      config: { ...DEFAULT_CONFIG }
+      tombstones: [],
+      devices: {},
+      config: { ...DEFAULT_CONFIG },
+      cursor: 0
     };
   }
   const payload = JSON.parse(row.payload_json);
@@ -40,14 +56,20 @@ export function getSnapshot(userId, since = '') {
   }
   let transacoes = payload.transacoes || [];
   let recorrentes = payload.recorrentes || [];
-  if (since) {
+  if (/^\d+$/.test(since)) {
+    transacoes = transacoes.filter((t) => (t.seq || 0) > Number(since));
+  } else if (since) {
     transacoes = transacoes.filter((t) => t.updatedAt && t.updatedAt >= since);
     // Recorrentes: sempre retorna a lista completa. Filtro por since perderia exclusões.
+    // Lápides também vão sempre completas.
   }
   return {
     transacoes,
     recorrentes,
-    config: { ...DEFAULT_CONFIG, ...config }
+    tombstones: payload.tombstones || [],
+    devices: payload.devices || {},
+    config: { ...DEFAULT_CONFIG, ...config },
+    cursor: payload.seq || 0
   };
 }
 
@@ -57,25 +79,72 @@ export function getSnapshot(userId, since = '') {
  * a versão com updatedAt mais recente prevalece.
  * O cliente deve garantir que transacoes e recorrentes tenham updatedAt antes do push.
  */
-function mergeByUpdatedAt(existing, incoming) {
+function mergeByUpdatedAt(existing, incoming, seq) {
   const byId = new Map(existing.map((t) => [t.id, t]));
   for (const t of incoming) {
     const cur = byId.get(t.id);
-    if (!cur || (t.updatedAt && cur.updatedAt && t.updatedAt > cur.updatedAt)) byId.set(t.id, t);
+    if (!cur || (t.updatedAt && cur.updatedAt && t.updatedAt > cur.updatedAt)) byId.set(t.id, { ...t, seq });
   }
   return Array.from(byId.values());
 }
 
+/**
+ * Lápides de exclusão definitiva. Cada aparelho reenvia no push as lápides que já aplicou,
+ * o que conta como confirmação (ackedBy). Uma lápide é expurgada quando todos os aparelhos
+ * ativos (push nos últimos 90 dias) confirmaram.
+ */
+function mergeTombstones(existing, incoming, deviceId, devices, agora) {
+  const byKey = new Map(existing.map((t) => [chaveLapide(t), { ...t, ackedBy: t.ackedBy || [] }]));
+  for (const t of incoming) {
+    if (!t || !TABELA_POR_ENTIDADE[t.entidade] || !t.id) continue;
+    const key = chaveLapide(t);
+    if (!byKey.has(key)) {
+      byKey.set(key, { entidade: t.entidade, id: t.id, deletedAt: t.deletedAt, deviceId: t.deviceId, ackedBy: [] });
+    }
+    const cur = byKey.get(key);
+    for (const d of [deviceId, t.deviceId]) {
+      if (d && !cur.ackedBy.includes(d)) cur.ackedBy.push(d);
+    }
+  }
+  const ativos = Object.entries(devices)
+    .filter(([, visto]) => agora - Date.parse(visto) < DEVICE_TTL_MS)
+    .map(([id]) => id);
+  return Array.from(byKey.values()).filter((t) => !ativos.every((d) => t.ackedBy.includes(d)));
+}
+
 /**
  * Salva snapshot. Transações: merge (last-write-wins). Recorrentes: substitui pela lista enviada
- * (o cliente envia sempre a lista completa; merge preservaria itens apagados no servidor).
- * Fase 2: TODO suporte a device_id e conflitos.
+ * (o cliente web envia sempre a lista completa; merge preservaria itens apagados no servidor).
+ * Push incremental (`delta`, app desktop) traz só o que mudou: recorrentes também entram por
+ * merge, já que exclusões chegam como lápides.
+ * Registros com lápide são descartados, para que um push antigo não os ressuscite.
  */
-export function saveSnapshot(userId, { transacoes = [], recorrentes = [], config = {} }) {
+export function saveSnapshot(userId, { transacoes = [], recorrentes = [], config = {}, tombstones = [], deviceId = null, delta = false }) {
   const db = getDb();
   const existing = getSnapshot(userId);
-  const mergedTransacoes = mergeByUpdatedAt(existing.transacoes, transacoes);
-  const mergedRecorrentes = Array.isArray(recorrentes) ? recorrentes : existing.recorrentes;
+  const seq = existing.cursor + 1;
+  const agora = Date.now();
+  const devices = { ...existing.devices };
+  if (deviceId) devices[deviceId] = new Date(agora).toISOString();
+  const mergedTombstones = mergeTombstones(
+    existing.tombstones,
+    Array.isArray(tombstones) ? tombstones : [],
+    deviceId,
+    devices,
+    agora
+  );
+  // Inclui as recém-expurgadas: todos os aparelhos já apagaram esses registros
+  const apagados = new Set(
+    [...existing.tombstones, ...(Array.isArray(tombstones) ? tombstones : [])].map(chaveLapide)
+  );
+  const vivo = (entidade) => (item) => !apagados.has(`${entidade}:${item.id}`);
+  const mergedTransacoes = mergeByUpdatedAt(existing.transacoes, transacoes, seq).filter(vivo('transacao'));
+  const recorrentesBase = !Array.isArray(recorrentes)
+    ? existing.recorrentes
+    : delta
+      ? mergeByUpdatedAt(existing.recorrentes, recorrentes, seq)
+      : recorrentes;
+  const mergedRecorrentes = recorrentesBase.filter(vivo('recorrencia'));
   const mergedConfig = { ...existing.config };
   if (config.categorias?.length) mergedConfig.categorias = config.categorias;
   if (config.contas?.length) mergedConfig.contas = config.contas;
@@ -87,7 +156,14 @@ export function saveSnapshot(userId, { transacoes = [], recorrentes = [], config
 
   const updatedAt = new Date().toISOString();
   mergedConfig.lastSyncedAt = updatedAt;
-  const payload = { transacoes: mergedTransacoes, recorrentes: mergedRecorrentes, config: mergedConfig };
+  const payload = {
+    seq,
+    transacoes: mergedTransacoes,
+    recorrentes: mergedRecorrentes,
+    tombstones: mergedTombstones,
+    devices,
+    config: mergedConfig
+  };
 
   db.prepare(
     'INSERT INTO snapshots (user_id, updated_at, payload_json, created_at) VALUES (?, ?, ?, ?)'
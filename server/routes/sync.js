@@ -13,14 +13,16 @@ router.get('/', (req, res) => {
   res.json({
     transacoes: store.transacoes,
     recorrentes: store.recorrentes,
-    config: store.config
+    tombstones: store.tombstones.map(({ entidade, id, deletedAt, deviceId }) => ({ entidade, id, deletedAt, deviceId })),
+    config: store.config,
+    cursor: store.cursor
   });
 });
 
 router.post('/', (req, res) => {
   const userId = req.user.userId;
-  const { transacoes = [], recorrentes = [], config = {} } = req.body;
-  syncRepo.saveSnapshot(userId, { transacoes, recorrentes, config });
+  const { transacoes = [], recorrentes = [], config = {}, tombstones = [], deviceId = null, delta = false } = req.body;
+  syncRepo.saveSnapshot(userId, { transacoes, recorrentes, config, tombstones, deviceId, delta: delta === true });
   res.json({ ok: true });
 });
 
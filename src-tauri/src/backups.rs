@@ -0,0 +1,308 @@
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use rusqlite::backup::Backup;
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+
+use crate::credenciais;
+use crate::criptografia;
+use crate::diagnostico;
+use crate::error::AppError;
+use crate::pool::{self, ChaveBanco, Pool};
+
+const PASTA: &str = "backups";
+const PREFIXO: &str = "vertexads-";
+const EXTENSAO: &str = ".db.gz";
+const FORMATO_DATA: &str = "%Y%m%d-%H%M%S";
+const INTERVALO_DIARIO: chrono::Duration = chrono::Duration::hours(24);
+// A cópia vai em passos, com pausa entre eles para as escritas não esperarem;
+// se o banco muda no meio, o SQLite recomeça a cópia sozinho.
+const PAGINAS_POR_PASSO: i32 = 1024;
+const PAUSA_ENTRE_PASSOS: Duration = Duration::from_millis(10);
+
+/// Por que o backup foi feito; cada motivo tem a sua retenção.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Motivo {
+    Diario,
+    Migracao,
+    Restauracao,
+    Manual,
+}
+
+impl Motivo {
+    const TODOS: [Motivo; 4] = [Motivo::Diario, Motivo::Migracao, Motivo::Restauracao, Motivo::Manual];
+
+    fn nome(self) -> &'static str {
+        match self {
+            Motivo::Diario => "diario",
+            Motivo::Migracao => "migracao",
+            Motivo::Restauracao => "restauracao",
+            Motivo::Manual => "manual",
+        }
+    }
+
+    // Quantos backups deste motivo ficam; os mais antigos são apagados.
+    fn retencao(self) -> usize {
+        match self {
+            Motivo::Diario => 7,
+            Motivo::Migracao => 3,
+            Motivo::Restauracao => 5,
+            Motivo::Manual => 10,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InfoBackup {
+    /// Nome do arquivo, usado para verificar e restaurar.
+    pub nome: String,
+    pub motivo: Motivo,
+    pub criado_em: String,
+    pub bytes: u64,
+}
+
+/// Resultado de `verificar_backup`. Falha na conferência não é erro do comando:
+/// volta com `ok: false`, o `code` do erro (ex: WRONG_KEY pede a senha) e a mensagem.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VerificacaoBackup {
+    pub nome: String,
+    pub ok: bool,
+    pub cifrado: bool,
+    pub code: Option<&'static str>,
+    pub erro: Option<String>,
+}
+
+fn erro_io(e: std::io::Error) -> AppError {
+    AppError::Internal(format!("backup: {}", e))
+}
+
+/// Pasta dos backups, ao lado do banco.
+pub fn pasta(db_path: &Path) -> PathBuf {
+    db_path.parent().unwrap_or_else(|| Path::new(".")).join(PASTA)
+}
+
+// vertexads-20261018-101500-diario.db.gz -> (data, motivo)
+fn ler_nome(nome: &str) -> Option<(chrono::NaiveDateTime, Motivo)> {
+    let meio = nome.strip_prefix(PREFIXO)?.strip_suffix(EXTENSAO)?;
+    let (data, motivo) = meio.rsplit_once('-')?;
+    let data = chrono::NaiveDateTime::parse_from_str(data, FORMATO_DATA).ok()?;
+    let motivo = Motivo::TODOS.into_iter().find(|m| m.nome() == motivo)?;
+    Some((data, motivo))
+}
+
+/// Backups na pasta, do mais novo para o mais antigo. Arquivos com outro nome são
+/// ignorados.
+pub fn listar(db_path: &Path) -> Result<Vec<InfoBackup>, AppError> {
+    let entradas = match std::fs::read_dir(pasta(db_path)) {
+        Ok(e) => e,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(erro_io(e)),
+    };
+    let mut lista: Vec<(chrono::NaiveDateTime, InfoBackup)> = entradas
+        .filter_map(|e| {
+            let e = e.ok()?;
+            let nome = e.file_name().into_string().ok()?;
+            let (data, motivo) = ler_nome(&nome)?;
+            let info = InfoBackup {
+                criado_em: data.and_utc().to_rfc3339(),
+                bytes: e.metadata().map(|m| m.len()).unwrap_or(0),
+                nome,
+                motivo,
+            };
+            Some((data, info))
+        })
+        .collect();
+    lista.sort_by_key(|(data, _)| std::cmp::Reverse(*data));
+    Ok(lista.into_iter().map(|(_, info)| info).collect())
+}
+
+/// Grava um backup de `conn` pela API de backup online do SQLite: uma cópia
+/// consistente, com a mesma chave do banco, compactada com gzip. Depois aplica a
+/// retenção do motivo. Um backup do mesmo motivo no mesmo segundo é reaproveitado.
+pub fn criar(conn: &Connection, db_path: &Path, chave: Option<&ChaveBanco>, motivo: Motivo) -> Result<InfoBackup, AppError> {
+    let dir = pasta(db_path);
+    std::fs::create_dir_all(&dir).map_err(erro_io)?;
+    let nome = format!("{}{}-{}{}", PREFIXO, chrono::Utc::now().format(FORMATO_DATA), motivo.nome(), EXTENSAO);
+    let destino = dir.join(&nome);
+    if !destino.exists() {
+        let copia = dir.join(format!(".{}.db", nome));
+        let parcial = dir.join(format!(".{}.parcial", nome));
+        let gravado = copiar(conn, &copia, chave).and_then(|_| compactar(&copia, &parcial));
+        let _ = std::fs::remove_file(&copia);
+        if let Err(e) = gravado.and_then(|_| std::fs::rename(&parcial, &destino).map_err(erro_io)) {
+            let _ = std::fs::remove_file(&parcial);
+            return Err(e);
+        }
+    }
+    podar(db_path, motivo);
+    listar(db_path)?
+        .into_iter()
+        .find(|b| b.nome == nome)
+        .ok_or_else(|| AppError::Internal(format!("backup {} sumiu", nome)))
+}
+
+/// Backup do banco aberto, lido por uma conexão de leitura: não segura as escritas.
+pub fn criar_do_pool(p: &Pool, motivo: Motivo) -> Result<InfoBackup, AppError> {
+    let chave = p.chave()?;
+    criar(&*p.leitura()?, p.path(), chave.as_ref(), motivo)
+}
+
+/// Faz o backup diário se o último tiver mais de 24 horas (ou não houver).
+pub fn diario(p: &Pool) -> Result<Option<InfoBackup>, AppError> {
+    let limite = chrono::Utc::now() - INTERVALO_DIARIO;
+    let recente = listar(p.path())?.iter().any(|b| {
+        b.motivo == Motivo::Diario
+            && chrono::DateTime::parse_from_rfc3339(&b.criado_em).is_ok_and(|d| d > limite)
+    });
+    if recente {
+        return Ok(None);
+    }
+    criar_do_pool(p, Motivo::Diario).map(Some)
+}
+
+// O SQLCipher só copia entre bancos com a mesma chave: o destino nasce com ela.
+fn copiar(conn: &Connection, destino: &Path, chave: Option<&ChaveBanco>) -> Result<(), AppError> {
+    let _ = std::fs::remove_file(destino);
+    let mut dst = Connection::open(destino)?;
+    if let Some(c) = chave {
+        dst.pragma_update(None, "key", c.pragma())?;
+    }
+    Backup::new(conn, &mut dst)?.run_to_completion(PAGINAS_POR_PASSO, PAUSA_ENTRE_PASSOS, None)?;
+    // O backup de migração sai antes de o token legado ir para o cofre
+    credenciais::apagar_de_config(&dst)
+}
+
+fn compactar(origem: &Path, destino: &Path) -> Result<(), AppError> {
+    let mut leitor = BufReader::new(File::open(origem).map_err(erro_io)?);
+    let mut gz = GzEncoder::new(BufWriter::new(File::create(destino).map_err(erro_io)?), Compression::default());
+    std::io::copy(&mut leitor, &mut gz).map_err(erro_io)?;
+    gz.finish().map_err(erro_io)?.into_inner().map_err(|e| erro_io(e.into_error()))?.sync_all().map_err(erro_io)
+}
+
+// Apaga os backups de `motivo` além da retenção. Falha aqui só vai para o log.
+fn podar(db_path: &Path, motivo: Motivo) {
+    let Ok(lista) = listar(db_path) else { return };
+    for velho in lista.iter().filter(|b| b.motivo == motivo).skip(motivo.retencao()) {
+        if let Err(e) = std::fs::remove_file(pasta(db_path).join(&velho.nome)) {
+            eprintln!("[Backup] não apagou {}: {}", velho.nome, e);
+        }
+    }
+}
+
+// Caminho de um backup da lista; nome com barra ou fora do padrão é recusado.
+fn localizar(db_path: &Path, nome: &str) -> Result<PathBuf, AppError> {
+    let path = pasta(db_path).join(nome);
+    if ler_nome(nome).is_none() || nome.contains(['/', '\\']) || !path.is_file() {
+        return Err(AppError::NotFound(format!("backup não encontrado: {}", nome)));
+    }
+    Ok(path)
+}
+
+/// Descompacta o backup `nome` num arquivo temporário da pasta de backups, para
+/// conferir ou restaurar. Quem chama apaga o arquivo com `descartar`.
+pub fn extrair(db_path: &Path, nome: &str) -> Result<PathBuf, AppError> {
+    let origem = localizar(db_path, nome)?;
+    let destino = pasta(db_path).join(format!(".{}.extraido", nome));
+    let mut gz = GzDecoder::new(BufReader::new(File::open(&origem).map_err(erro_io)?));
+    let copia = File::create(&destino)
+        .and_then(|f| {
+            let mut w = BufWriter::new(f);
+            std::io::copy(&mut gz, &mut w)?;
+            w.into_inner().map_err(|e| e.into_error())?.sync_all()
+        })
+        .map_err(|e| AppError::field("nome", format!("backup ilegível: {}", e)));
+    if let Err(e) = copia {
+        descartar(&destino);
+        return Err(e);
+    }
+    Ok(destino)
+}
+
+/// Apaga o arquivo de `extrair`, com o -wal e o -shm que abri-lo deixou.
+pub fn descartar(extraido: &Path) {
+    for sufixo in ["", "-wal", "-shm"] {
+        let mut nome = extraido.as_os_str().to_owned();
+        nome.push(sufixo);
+        let _ = std::fs::remove_file(PathBuf::from(nome));
+    }
+}
+
+/// Descompacta e confere o backup (chave, versão do schema, integrity_check) sem
+/// tocar no banco em uso.
+pub fn verificar(db_path: &Path, nome: &str, senha: Option<String>, atual: Option<&Pool>) -> Result<VerificacaoBackup, AppError> {
+    let extraido = extrair(db_path, nome)?;
+    let cifrado = criptografia::cifrado(&extraido);
+    let conferido = diagnostico::conferir_backup(&extraido, senha, atual);
+    descartar(&extraido);
+    let (ok, code, erro) = match conferido {
+        Ok(_) => (true, None, None),
+        Err(e) => (false, Some(e.code()), Some(e.to_string())),
+    };
+    Ok(VerificacaoBackup { nome: nome.to_string(), ok, cifrado, code, erro })
+}
+
+/// Backup já regravado com a chave nova num arquivo temporário, à espera da troca
+/// do banco para ir para o lugar do original.
+pub struct Recifrado {
+    temporario: PathBuf,
+    destino: PathBuf,
+}
+
+/// Regrava cada backup com a chave `nova` em arquivos temporários, sem tocar nos
+/// originais: o banco ainda pode não trocar de chave. Backup que não abre com
+/// `antiga` (ou está corrompido) já não abria antes e fica como está; falha de
+/// disco cancela tudo.
+pub fn recifrar(db_path: &Path, antiga: Option<&ChaveBanco>, nova: Option<&ChaveBanco>) -> Result<Vec<Recifrado>, AppError> {
+    let dir = pasta(db_path);
+    let mut prontos = vec![];
+    for b in listar(db_path)? {
+        let copia = dir.join(format!(".{}.db", b.nome));
+        let temporario = dir.join(format!(".{}.recifrado", b.nome));
+        let feito = extrair(db_path, &b.nome).and_then(|extraido| {
+            let r = pool::recifrar_arquivo(&extraido, antiga, &copia, nova).and_then(|_| compactar(&copia, &temporario));
+            descartar(&extraido);
+            r
+        });
+        descartar(&copia);
+        match feito {
+            Ok(()) => prontos.push(Recifrado { temporario, destino: dir.join(&b.nome) }),
+            Err(e @ (AppError::WrongKey(_) | AppError::DbUnavailable(_) | AppError::Validation(_))) => {
+                let _ = std::fs::remove_file(&temporario);
+                eprintln!("[Backup] {} ilegível ou com outra chave, fica como está: {}", b.nome, e);
+            }
+            Err(e) => {
+                let _ = std::fs::remove_file(&temporario);
+                abandonar(prontos);
+                return Err(e);
+            }
+        }
+    }
+    Ok(prontos)
+}
+
+/// Põe os backups regravados no lugar dos originais, depois que o banco trocou de
+/// chave. Falha aqui só vai para o log.
+pub fn efetivar(recifrados: Vec<Recifrado>) {
+    for r in recifrados {
+        if let Err(e) = std::fs::rename(&r.temporario, &r.destino) {
+            eprintln!("[Backup] não regravou {}: {}", r.destino.display(), e);
+            let _ = std::fs::remove_file(&r.temporario);
+        }
+    }
+}
+
+/// Apaga os temporários de `recifrar` quando o banco não trocou de chave.
+pub fn abandonar(recifrados: Vec<Recifrado>) {
+    for r in recifrados {
+        let _ = std::fs::remove_file(&r.temporario);
+    }
+}
@@ -0,0 +1,273 @@
+use std::sync::Mutex;
+
+use argon2::password_hash::rand_core::OsRng;
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+
+use crate::db;
+use crate::error::AppError;
+
+// Fica na tabela config, fora do change_log: é deste aparelho e não vai no sync.
+const CONFIG_KEY: &str = "bloqueio";
+const SEGREDO_MINIMO: usize = 4;
+const INATIVIDADE_MAXIMA_MIN: u32 = 24 * 60;
+// Erros seguidos aceitos sem espera; a partir daí cada erro dobra a espera.
+const TENTATIVAS_LIVRES: u32 = 5;
+const ESPERA_INICIAL_SECS: i64 = 30;
+const ESPERA_MAXIMA_SECS: i64 = 15 * 60;
+
+/// Quando o app se bloqueia sozinho.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PreferenciasBloqueio {
+    /// Minutos sem uso até bloquear; 0 desliga.
+    pub inatividade_min: u32,
+    /// Bloqueia quando a janela perde o foco.
+    pub ao_perder_foco: bool,
+}
+
+impl Default for PreferenciasBloqueio {
+    fn default() -> Self {
+        PreferenciasBloqueio { inatividade_min: 5, ao_perder_foco: true }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Guardado {
+    /// Hash Argon2id no formato PHC, com o sal.
+    hash: Option<String>,
+    #[serde(default)]
+    preferencias: PreferenciasBloqueio,
+    #[serde(default)]
+    falhas: u32,
+    /// Até quando (segundos desde a época) novas tentativas são recusadas.
+    espera_ate: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EstadoBloqueio {
+    /// Há PIN ou senha definido; sem ele o app nunca bloqueia.
+    pub configurado: bool,
+    pub bloqueado: bool,
+    pub preferencias: PreferenciasBloqueio,
+    pub falhas: u32,
+    /// Segundos até aceitar outra tentativa, depois de erros demais.
+    pub espera_secs: Option<i64>,
+}
+
+/// Payload de `bloqueio://bloqueado`, emitido quando o app bloqueia sozinho.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Motivo {
+    Manual,
+    Inatividade,
+    Foco,
+}
+
+#[derive(Default)]
+struct Estado {
+    guardado: Guardado,
+    bloqueado: bool,
+    // Relógio de parede em ms: o tempo com o computador suspenso conta como inatividade
+    ultima_atividade: i64,
+}
+
+impl Estado {
+    fn configurado(&self) -> bool {
+        self.guardado.hash.is_some()
+    }
+
+    fn ocioso(&self, agora: i64) -> bool {
+        let limite = self.guardado.preferencias.inatividade_min;
+        limite > 0 && agora - self.ultima_atividade >= i64::from(limite) * 60_000
+    }
+}
+
+/// Bloqueio do app por PIN ou senha, para quem senta num computador destravado
+/// não ver os livros. Só trava os comandos: o banco continua aberto e o sync em
+/// segundo plano continua rodando.
+#[derive(Default)]
+pub struct Bloqueio {
+    estado: Mutex<Estado>,
+    // Serializa as tentativas, para a contagem de erros valer também em paralelo
+    tentativa: Mutex<()>,
+}
+
+fn agora_ms() -> i64 {
+    chrono::Utc::now().timestamp_millis()
+}
+
+fn erro_hash(e: argon2::password_hash::Error) -> AppError {
+    AppError::Internal(format!("hash do bloqueio: {}", e))
+}
+
+fn gerar_hash(segredo: &str) -> Result<String, AppError> {
+    if segredo.chars().count() < SEGREDO_MINIMO {
+        return Err(AppError::field("novo", format!("mínimo de {} caracteres", SEGREDO_MINIMO)));
+    }
+    let sal = SaltString::generate(&mut OsRng);
+    Ok(Argon2::default().hash_password(segredo.as_bytes(), &sal).map_err(erro_hash)?.to_string())
+}
+
+impl Bloqueio {
+    /// Lê a configuração do banco recém-aberto; com `bloquear`, começa bloqueado
+    /// se houver PIN.
+    pub fn carregar(&self, conn: &Connection, bloquear: bool) -> Result<(), AppError> {
+        let guardado: Guardado = db::config_value(conn, CONFIG_KEY);
+        let mut e = self.estado.lock()?;
+        e.bloqueado = bloquear && guardado.hash.is_some();
+        e.guardado = guardado;
+        e.ultima_atividade = agora_ms();
+        Ok(())
+    }
+
+    /// Trava dos comandos de dados: recusa com o app bloqueado (ou parado além do
+    /// limite, que bloqueia agora) e, liberado, conta como atividade.
+    pub fn verificar(&self) -> Result<(), AppError> {
+        let mut e = self.estado.lock()?;
+        let agora = agora_ms();
+        if e.configurado() && e.ocioso(agora) {
+            e.bloqueado = true;
+        }
+        if e.bloqueado {
+            return Err(AppError::Locked("app bloqueado: informe o PIN ou a senha".to_string()));
+        }
+        e.ultima_atividade = agora;
+        Ok(())
+    }
+
+    /// Bloqueia se houver PIN e o `motivo` valer pelas preferências. Devolve se
+    /// bloqueou agora (já bloqueado não conta).
+    pub fn bloquear(&self, motivo: Motivo) -> bool {
+        let Ok(mut e) = self.estado.lock() else { return false };
+        let vale = match motivo {
+            Motivo::Manual => true,
+            Motivo::Inatividade => e.ocioso(agora_ms()),
+            Motivo::Foco => e.guardado.preferencias.ao_perder_foco,
+        };
+        if !vale || !e.configurado() || e.bloqueado {
+            return false;
+        }
+        e.bloqueado = true;
+        true
+    }
+
+    pub fn estado(&self) -> Result<EstadoBloqueio, AppError> {
+        let e = self.estado.lock()?;
+        let agora = chrono::Utc::now().timestamp();
+        Ok(EstadoBloqueio {
+            configurado: e.configurado(),
+            bloqueado: e.bloqueado || (e.configurado() && e.ocioso(agora_ms())),
+            preferencias: e.guardado.preferencias,
+            falhas: e.guardado.falhas,
+            espera_secs: e.guardado.espera_ate.map(|t| t - agora).filter(|s| *s > 0),
+        })
+    }
+
+    /// Confere o PIN ou a senha e desbloqueia. A contagem de erros fica no banco,
+    /// então reabrir o app não zera a espera.
+    pub fn desbloquear(&self, conn: &Connection, segredo: &str) -> Result<(), AppError> {
+        let _vez = self.tentativa.lock()?;
+        if !self.estado.lock()?.bloqueado {
+            return Ok(());
+        }
+        self.conferir(conn, segredo)?;
+        let mut e = self.estado.lock()?;
+        e.bloqueado = false;
+        e.ultima_atividade = agora_ms();
+        Ok(())
+    }
+
+    /// Define, troca ou remove (`novo` = `None`) o PIN ou a senha. Com um já
+    /// definido, `atual` precisa conferir, com o mesmo limite de tentativas.
+    pub fn definir_segredo(&self, conn: &Connection, atual: Option<&str>, novo: Option<&str>) -> Result<(), AppError> {
+        let _vez = self.tentativa.lock()?;
+        let hash = novo.map(gerar_hash).transpose()?;
+        if self.estado.lock()?.configurado() {
+            let atual = atual.ok_or_else(|| AppError::field("atual", "obrigatório"))?;
+            self.conferir(conn, atual)?;
+        }
+        let mut guardado = self.estado.lock()?.guardado.clone();
+        guardado.hash = hash;
+        self.gravar(conn, guardado)
+    }
+
+    pub fn set_preferencias(&self, conn: &Connection, preferencias: PreferenciasBloqueio) -> Result<(), AppError> {
+        if preferencias.inatividade_min > INATIVIDADE_MAXIMA_MIN {
+            return Err(AppError::field("inatividadeMin", format!("máximo de {} minutos", INATIVIDADE_MAXIMA_MIN)));
+        }
+        let _vez = self.tentativa.lock()?;
+        let mut guardado = self.estado.lock()?.guardado.clone();
+        guardado.preferencias = preferencias;
+        self.gravar(conn, guardado)
+    }
+
+    // Confere `segredo` com o hash guardado. Depois de `TENTATIVAS_LIVRES` erros
+    // seguidos, cada erro dobra a espera até a próxima tentativa. Chamar com
+    // `tentativa` preso.
+    fn conferir(&self, conn: &Connection, segredo: &str) -> Result<(), AppError> {
+        let mut guardado = self.estado.lock()?.guardado.clone();
+        let Some(hash) = guardado.hash.as_deref() else { return Ok(()) };
+        let agora = chrono::Utc::now().timestamp();
+        if let Some(espera) = guardado.espera_ate.map(|t| t - agora).filter(|s| *s > 0) {
+            return Err(AppError::RateLimited(format!("tentativas erradas demais: tente de novo em {} s", espera)));
+        }
+        let confere = Argon2::default().verify_password(segredo.as_bytes(), &PasswordHash::new(hash).map_err(erro_hash)?).is_ok();
+        let antes = (guardado.falhas, guardado.espera_ate);
+        if confere {
+            guardado.falhas = 0;
+            guardado.espera_ate = None;
+        } else {
+            guardado.falhas += 1;
+            if guardado.falhas >= TENTATIVAS_LIVRES {
+                let dobras = (guardado.falhas - TENTATIVAS_LIVRES).min(16);
+                guardado.espera_ate = Some(agora + (ESPERA_INICIAL_SECS << dobras).min(ESPERA_MAXIMA_SECS));
+            }
+        }
+        if (guardado.falhas, guardado.espera_ate) != antes {
+            self.gravar(conn, guardado)?;
+        }
+        if confere {
+            Ok(())
+        } else {
+            Err(AppError::WrongKey("PIN ou senha incorreta".to_string()))
+        }
+    }
+
+    fn gravar(&self, conn: &Connection, guardado: Guardado) -> Result<(), AppError> {
+        let value = serde_json::to_string(&guardado).map_err(|e| AppError::Internal(e.to_string()))?;
+        db::put_config_value(conn, CONFIG_KEY, &value)?;
+        self.estado.lock()?.guardado = guardado;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn erros_demais_travam_as_tentativas_mesmo_depois_de_reabrir() {
+        let conn = Connection::open_in_memory().unwrap();
+        db::migrate(&conn).unwrap();
+        let b = Bloqueio::default();
+        b.definir_segredo(&conn, None, Some("1234")).unwrap();
+        assert!(b.bloquear(Motivo::Manual));
+        for _ in 0..TENTATIVAS_LIVRES {
+            assert!(matches!(b.desbloquear(&conn, "0000"), Err(AppError::WrongKey(_))));
+        }
+        let estado = b.estado().unwrap();
+        assert_eq!(estado.falhas, TENTATIVAS_LIVRES);
+        assert!(estado.espera_secs.is_some_and(|s| s > 0 && s <= ESPERA_INICIAL_SECS));
+        // Na espera, nem o PIN certo passa
+        assert!(matches!(b.desbloquear(&conn, "1234"), Err(AppError::RateLimited(_))));
+        assert!(b.estado().unwrap().bloqueado);
+        let reaberto = Bloqueio::default();
+        reaberto.carregar(&conn, true).unwrap();
+        assert!(matches!(reaberto.desbloquear(&conn, "1234"), Err(AppError::RateLimited(_))));
+    }
+}
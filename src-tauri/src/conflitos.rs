@@ -0,0 +1,284 @@
+use std::collections::BTreeSet;
+
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::db::{self, now_iso, version_millis, Escrita};
+use crate::error::AppError;
+use crate::models::{Entidade, Recorrencia, Transacao};
+use crate::recorrencias;
+
+const VERSAO: &str = "updatedAt";
+// Campos que descrevem a edição, não o registro; ficam fora da comparação.
+const METADADOS: [&str; 2] = [VERSAO, "origemDevice"];
+
+/// Registro editado aqui e no servidor, no mesmo campo, desde o último sync.
+/// Enquanto não é resolvido, a versão local fica no banco e não vai no push.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Conflito {
+    pub entidade: Entidade,
+    pub id: String,
+    pub local: Value,
+    pub remoto: Value,
+    pub campos: Vec<String>,
+    pub detectado_em: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Lado {
+    Local,
+    Remoto,
+}
+
+#[derive(Debug, PartialEq)]
+enum Decisao {
+    AplicarRemoto,
+    ManterLocal,
+    Mesclar(Value),
+    Conflito(Vec<String>),
+}
+
+fn sem_metadados(v: &Value) -> Map<String, Value> {
+    let mut m = v.as_object().cloned().unwrap_or_default();
+    for k in METADADOS {
+        m.remove(k);
+    }
+    m
+}
+
+/// Campos com valor diferente entre duas versões do mesmo registro, sem metadados.
+pub fn campos_diferentes(a: &Value, b: &Value) -> Vec<String> {
+    let (a, b) = (sem_metadados(a), sem_metadados(b));
+    let chaves: BTreeSet<&String> = a.keys().chain(b.keys()).collect();
+    chaves.into_iter().filter(|k| a.get(*k) != b.get(*k)).cloned().collect()
+}
+
+fn versao(v: &Value) -> Option<i64> {
+    v.get(VERSAO).and_then(Value::as_str).and_then(version_millis)
+}
+
+// Política do pull, por registro:
+// - sem edição local pendente, o remoto é aplicado;
+// - com edição pendente e base conhecida, merge de três vias por campo: cada lado
+//   leva os campos que só ele mudou; o mesmo campo mudado para valores diferentes
+//   nos dois lados é conflito;
+// - com edição pendente e sem base (registro nunca sincronizado por este merge),
+//   vence o `updatedAt` mais recente, e o local em caso de empate.
+fn decidir(local: Option<&Value>, remoto: &Value, base: Option<&Value>, pendente: bool) -> Decisao {
+    let Some(local) = local else { return Decisao::AplicarRemoto };
+    let (l, r) = (sem_metadados(local), sem_metadados(remoto));
+    if l == r {
+        return Decisao::ManterLocal;
+    }
+    if !pendente {
+        return Decisao::AplicarRemoto;
+    }
+    let Some(base) = base else {
+        return if versao(remoto) > versao(local) { Decisao::AplicarRemoto } else { Decisao::ManterLocal };
+    };
+    let b = sem_metadados(base);
+    let mut mesclado = Map::new();
+    let mut campos = vec![];
+    let chaves: BTreeSet<&String> = l.keys().chain(r.keys()).collect();
+    for k in chaves {
+        let (lv, rv, bv) = (l.get(k), r.get(k), b.get(k));
+        let (mudou_local, mudou_remoto) = (lv != bv, rv != bv);
+        if mudou_local && mudou_remoto && lv != rv {
+            campos.push(k.clone());
+        }
+        if let Some(v) = if mudou_local { lv } else { rv } {
+            mesclado.insert(k.clone(), v.clone());
+        }
+    }
+    if !campos.is_empty() {
+        Decisao::Conflito(campos)
+    } else if mesclado == r {
+        Decisao::AplicarRemoto
+    } else {
+        Decisao::Mesclar(Value::Object(mesclado))
+    }
+}
+
+fn ler_json(conn: &Connection, sql: &str, entidade: Entidade, id: &str) -> Result<Option<Value>, AppError> {
+    let texto: Option<String> = conn.query_row(sql, params![entidade, id], |r| r.get(0)).ok();
+    Ok(texto.and_then(|t| serde_json::from_str(&t).ok()))
+}
+
+fn salvar_base(conn: &Connection, entidade: Entidade, id: &str, dados: &Value) -> Result<(), AppError> {
+    conn.execute(
+        "INSERT OR REPLACE INTO sync_base (entidade, id, dados) VALUES (?1, ?2, ?3)",
+        params![entidade, id, dados.to_string()],
+    )?;
+    Ok(())
+}
+
+fn ler_local(conn: &Connection, entidade: Entidade, id: &str) -> Result<Option<Value>, AppError> {
+    let v = match entidade {
+        Entidade::Transacao => db::get_transacao(conn, id)?.map(serde_json::to_value),
+        Entidade::Recorrencia => db::get_recorrencia(conn, id)?.map(serde_json::to_value),
+    };
+    v.transpose().map_err(|e| AppError::Internal(e.to_string()))
+}
+
+// Grava a versão escolhida do registro, validada. Os lançamentos que uma recorrência
+// gera ou ajusta são sempre escrita local deste aparelho.
+fn gravar(conn: &Connection, entidade: Entidade, dados: &Value, escrita: Escrita) -> Result<(), AppError> {
+    let payload_err = |e: serde_json::Error| AppError::field("payload", e.to_string());
+    match entidade {
+        Entidade::Transacao => {
+            let tx: Transacao = serde_json::from_value(dados.clone()).map_err(payload_err)?;
+            tx.validate()?;
+            db::write_transacao(conn, &tx, escrita)?;
+        }
+        Entidade::Recorrencia => {
+            let r: Recorrencia = serde_json::from_value(dados.clone()).map_err(payload_err)?;
+            r.validate()?;
+            db::write_recorrencia(conn, &r, escrita)?;
+            let updated_at = match escrita {
+                Escrita::Local { updated_at } => updated_at.to_string(),
+                Escrita::Replicada | Escrita::Importada => now_iso(),
+            };
+            recorrencias::propagar_alteracao(conn, &r, &updated_at)?;
+        }
+    }
+    Ok(())
+}
+
+// Grava como edição local nova: `updated_at` de agora, mesmo que os dados já sejam
+// os do banco, para o push vencer o last-write-wins do servidor.
+fn gravar_edicao_local(conn: &Connection, entidade: Entidade, dados: &Value) -> Result<(), AppError> {
+    let agora = now_iso();
+    gravar(conn, entidade, dados, Escrita::Local { updated_at: &agora })?;
+    let id = dados.get("id").and_then(Value::as_str).unwrap_or_default();
+    conn.execute(&format!("UPDATE {} SET updated_at = ?1 WHERE id = ?2", entidade.tabela()), params![agora, id])?;
+    db::mark_changed(conn, entidade.as_str(), id)?;
+    Ok(())
+}
+
+/// Aplica um registro vindo do pull segundo a política de `decidir`.
+/// Deve rodar dentro da transação de quem aplica o payload.
+pub fn aplicar_remoto(conn: &Connection, entidade: Entidade, remoto: &Value) -> Result<(), AppError> {
+    let id = remoto.get("id").and_then(Value::as_str).unwrap_or_default().to_string();
+    let local = ler_local(conn, entidade, &id)?;
+    let base = ler_json(conn, "SELECT dados FROM sync_base WHERE entidade = ?1 AND id = ?2", entidade, &id)?;
+    let pendente = db::is_pending(conn, entidade.as_str(), &id)?;
+    match decidir(local.as_ref(), remoto, base.as_ref(), pendente) {
+        Decisao::AplicarRemoto => {
+            gravar(conn, entidade, remoto, Escrita::Replicada)?;
+            db::clear_pending(conn, entidade.as_str(), &id)?;
+            salvar_base(conn, entidade, &id, remoto)?;
+            conn.execute("DELETE FROM conflitos WHERE entidade = ?1 AND id = ?2", params![entidade, id])?;
+        }
+        Decisao::ManterLocal => salvar_base(conn, entidade, &id, remoto)?,
+        Decisao::Mesclar(dados) => {
+            gravar_edicao_local(conn, entidade, &dados)?;
+            salvar_base(conn, entidade, &id, remoto)?;
+        }
+        Decisao::Conflito(campos) => {
+            conn.execute(
+                "INSERT OR REPLACE INTO conflitos (entidade, id, local, remoto, campos, detectado_em)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![
+                    entidade,
+                    id,
+                    local.unwrap_or_default().to_string(),
+                    remoto.to_string(),
+                    Value::from(campos).to_string(),
+                    now_iso()
+                ],
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// Depois de um push confirmado, o que foi enviado passa a ser a base do merge.
+pub fn registrar_enviado(conn: &Connection, entidade: Entidade, dados: &Value) -> Result<(), AppError> {
+    let id = dados.get("id").and_then(Value::as_str).unwrap_or_default();
+    salvar_base(conn, entidade, id, dados)
+}
+
+pub fn list_conflitos(conn: &Connection) -> Result<Vec<Conflito>, AppError> {
+    let mut stmt = conn.prepare(
+        "SELECT entidade, id, local, remoto, campos, detectado_em FROM conflitos ORDER BY detectado_em",
+    )?;
+    let rows = stmt.query_map([], |r| {
+        let json = |i: usize| -> rusqlite::Result<Value> {
+            Ok(serde_json::from_str(&r.get::<_, String>(i)?).unwrap_or_default())
+        };
+        Ok(Conflito {
+            entidade: r.get(0)?,
+            id: r.get(1)?,
+            local: json(2)?,
+            remoto: json(3)?,
+            campos: serde_json::from_value(json(4)?).unwrap_or_default(),
+            detectado_em: r.get(5)?,
+        })
+    })?;
+    let mut out = vec![];
+    for r in rows {
+        out.push(r?);
+    }
+    Ok(out)
+}
+
+/// Resolve um conflito. `Local` regrava a versão local como edição nova, que vai no
+/// próximo push; `Remoto` aplica a do servidor e descarta a edição local pendente.
+pub fn resolver_conflito(conn: &Connection, entidade: Entidade, id: &str, lado: Lado) -> Result<(), AppError> {
+    let db_tx = conn.unchecked_transaction()?;
+    let sql = "SELECT local FROM conflitos WHERE entidade = ?1 AND id = ?2";
+    let local = ler_json(&db_tx, sql, entidade, id)?
+        .ok_or_else(|| AppError::NotFound(format!("conflito não encontrado: {}", id)))?;
+    let remoto = ler_json(&db_tx, "SELECT remoto FROM conflitos WHERE entidade = ?1 AND id = ?2", entidade, id)?
+        .unwrap_or_default();
+    match lado {
+        Lado::Local => gravar_edicao_local(&db_tx, entidade, &local)?,
+        Lado::Remoto => {
+            gravar(&db_tx, entidade, &remoto, Escrita::Replicada)?;
+            db::clear_pending(&db_tx, entidade.as_str(), id)?;
+        }
+    }
+    salvar_base(&db_tx, entidade, id, &remoto)?;
+    db_tx.execute("DELETE FROM conflitos WHERE entidade = ?1 AND id = ?2", params![entidade, id])?;
+    db_tx.commit()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn registro(description: &str, value: i64, updated_at: &str) -> Value {
+        json!({ "id": "t1", "description": description, "value": value, "updatedAt": updated_at })
+    }
+
+    #[test]
+    fn mesmo_campo_mudado_dos_dois_lados_e_conflito() {
+        let base = registro("aluguel", 1000, "2026-01-01T00:00:00.000Z");
+        let local = registro("aluguel", 1200, "2026-01-02T00:00:00.000Z");
+        let remoto = registro("aluguel", 1500, "2026-01-03T00:00:00.000Z");
+        assert_eq!(decidir(Some(&local), &remoto, Some(&base), true), Decisao::Conflito(vec!["value".to_string()]));
+    }
+
+    #[test]
+    fn campos_diferentes_se_mesclam() {
+        let base = registro("aluguel", 1000, "2026-01-01T00:00:00.000Z");
+        let local = registro("aluguel sala", 1000, "2026-01-02T00:00:00.000Z");
+        let remoto = registro("aluguel", 1500, "2026-01-03T00:00:00.000Z");
+        let Decisao::Mesclar(mesclado) = decidir(Some(&local), &remoto, Some(&base), true) else {
+            panic!("esperava merge");
+        };
+        assert_eq!(mesclado, json!({ "id": "t1", "description": "aluguel sala", "value": 1500 }));
+    }
+
+    #[test]
+    fn sem_edicao_pendente_o_remoto_vence() {
+        let local = registro("aluguel", 1200, "2026-01-02T00:00:00.000Z");
+        let remoto = registro("aluguel", 1500, "2026-01-01T00:00:00.000Z");
+        assert_eq!(decidir(Some(&local), &remoto, None, false), Decisao::AplicarRemoto);
+    }
+}
@@ -0,0 +1,291 @@
+use chrono::{Datelike, Duration, NaiveDate};
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+
+use crate::error::AppError;
+use crate::models::{is_valid_date, TipoConta, ValidationErrors};
+use crate::money::Money;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Conta {
+    pub nome: String,
+    #[serde(default)]
+    pub tipo: TipoConta,
+    #[serde(default)]
+    pub saldo_inicial: Money,
+    /// Data do saldo inicial; lançamentos anteriores a ela não entram no saldo.
+    pub data_saldo_inicial: Option<String>,
+    #[serde(default = "default_true")]
+    pub ativo: bool,
+    pub updated_at: Option<String>,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+impl Conta {
+    pub fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::default();
+        if self.nome.trim().is_empty() {
+            errors.add("nome", "obrigatório");
+        }
+        if let Some(d) = &self.data_saldo_inicial {
+            if !is_valid_date(d) {
+                errors.add("dataSaldoInicial", "data inválida, use YYYY-MM-DD");
+            }
+        }
+        errors.into_result()
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SaldoConta {
+    pub conta: String,
+    pub tipo: TipoConta,
+    pub data: String,
+    /// Saldo realizado: saldo inicial + lançamentos pagos até a data.
+    pub saldo: Money,
+    /// Saldo realizado somado aos lançamentos previstos até a data.
+    pub saldo_projetado: Money,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Granularidade {
+    #[default]
+    Diaria,
+    Mensal,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PontoSaldo {
+    pub data: String,
+    pub saldo: Money,
+    pub saldo_projetado: Money,
+}
+
+pub fn today() -> NaiveDate {
+    chrono::Local::now().date_naive()
+}
+
+pub fn parse_date(field: &str, s: &str) -> Result<NaiveDate, AppError> {
+    s.get(..10)
+        .and_then(|d| NaiveDate::parse_from_str(d, "%Y-%m-%d").ok())
+        .ok_or_else(|| AppError::field(field, "data inválida, use YYYY-MM-DD"))
+}
+
+fn conta_from_row(row: &rusqlite::Row) -> rusqlite::Result<Conta> {
+    Ok(Conta {
+        nome: row.get(0)?,
+        tipo: row.get(1)?,
+        saldo_inicial: row.get(2)?,
+        data_saldo_inicial: row.get(3)?,
+        ativo: row.get(4)?,
+        updated_at: row.get(5)?,
+    })
+}
+
+pub fn list_contas(conn: &Connection) -> Result<Vec<Conta>, AppError> {
+    let mut stmt = conn.prepare(
+        "SELECT nome, tipo, saldo_inicial, data_saldo_inicial, ativo, updated_at FROM contas ORDER BY nome",
+    )?;
+    let rows = stmt.query_map([], conta_from_row)?;
+    let mut out = vec![];
+    for r in rows {
+        out.push(r?);
+    }
+    Ok(out)
+}
+
+fn get_conta(conn: &Connection, nome: &str) -> Result<Conta, AppError> {
+    conn.query_row(
+        "SELECT nome, tipo, saldo_inicial, data_saldo_inicial, ativo, updated_at FROM contas WHERE nome = ?1",
+        [nome],
+        conta_from_row,
+    )
+    .map_err(|e| match e {
+        rusqlite::Error::QueryReturnedNoRows => AppError::NotFound(format!("conta não encontrada: {}", nome)),
+        e => e.into(),
+    })
+}
+
+/// Grava a conta como veio. O cadastro fica só neste aparelho (fora do change_log);
+/// o que sincroniza são os nomes nas listas de config, via `ensure_conta`.
+pub fn put_conta(conn: &Connection, conta: &Conta, updated_at: &str) -> Result<(), AppError> {
+    conta.validate()?;
+    conn.execute(
+        "INSERT OR REPLACE INTO contas (nome, tipo, saldo_inicial, data_saldo_inicial, ativo, updated_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params![conta.nome.trim(), conta.tipo, conta.saldo_inicial, conta.data_saldo_inicial, conta.ativo, updated_at],
+    )?;
+    Ok(())
+}
+
+/// Garante o cadastro de contas citadas em lançamentos ou nas listas de config,
+/// sem alterar as já existentes.
+pub fn ensure_conta(conn: &Connection, nome: &str, tipo: TipoConta) -> Result<(), rusqlite::Error> {
+    let nome = nome.trim();
+    if !nome.is_empty() {
+        conn.execute("INSERT OR IGNORE INTO contas (nome, tipo) VALUES (?1, ?2)", params![nome, tipo])?;
+    }
+    Ok(())
+}
+
+// Movimento líquido (entradas - saídas) da conta desde a data do saldo inicial
+// até `ate` (inclusivo), separado em pago e previsto.
+fn movimento(conn: &Connection, conta: &Conta, ate: NaiveDate) -> Result<(Money, Money), AppError> {
+    let ate = (ate + Duration::days(1)).format("%Y-%m-%d").to_string();
+    Ok(conn.query_row(
+        "SELECT
+            COALESCE(SUM(CASE WHEN status = 'pago' THEN CASE type WHEN 'entrada' THEN value ELSE -value END END), 0),
+            COALESCE(SUM(CASE WHEN status = 'previsto' THEN CASE type WHEN 'entrada' THEN value ELSE -value END END), 0)
+         FROM transacoes
+         WHERE deleted = 0 AND account = ?1 AND data >= COALESCE(?2, '') AND data < ?3",
+        params![conta.nome, conta.data_saldo_inicial, ate],
+        |r| Ok((r.get(0)?, r.get(1)?)),
+    )?)
+}
+
+pub fn saldo_em(conn: &Connection, conta: &Conta, data: NaiveDate) -> Result<SaldoConta, AppError> {
+    let (pago, previsto) = movimento(conn, conta, data)?;
+    let saldo = conta.saldo_inicial + pago;
+    Ok(SaldoConta {
+        conta: conta.nome.clone(),
+        tipo: conta.tipo,
+        data: data.format("%Y-%m-%d").to_string(),
+        saldo,
+        saldo_projetado: saldo + previsto,
+    })
+}
+
+/// Saldo de cada conta ativa na data informada (hoje, se ausente).
+pub fn saldos(conn: &Connection, data: Option<&str>) -> Result<Vec<SaldoConta>, AppError> {
+    let data = match data {
+        Some(d) => parse_date("data", d)?,
+        None => today(),
+    };
+    list_contas(conn)?
+        .iter()
+        .filter(|c| c.ativo)
+        .map(|c| saldo_em(conn, c, data))
+        .collect()
+}
+
+pub fn saldo_conta(conn: &Connection, nome: &str, data: Option<&str>) -> Result<SaldoConta, AppError> {
+    let data = match data {
+        Some(d) => parse_date("data", d)?,
+        None => today(),
+    };
+    saldo_em(conn, &get_conta(conn, nome)?, data)
+}
+
+pub fn last_day_of_month(d: NaiveDate) -> NaiveDate {
+    let (y, m) = if d.month() == 12 { (d.year() + 1, 1) } else { (d.year(), d.month() + 1) };
+    NaiveDate::from_ymd_opt(y, m, 1).map(|n| n - Duration::days(1)).unwrap_or(d)
+}
+
+/// Série de saldos de uma conta: um ponto por dia, ou por fim de mês, entre as datas.
+pub fn serie_saldo(
+    conn: &Connection,
+    nome: &str,
+    date_from: &str,
+    date_to: &str,
+    granularidade: Granularidade,
+) -> Result<Vec<PontoSaldo>, AppError> {
+    let conta = get_conta(conn, nome)?;
+    let inicio = parse_date("dateFrom", date_from)?;
+    let fim = parse_date("dateTo", date_to)?;
+    if fim < inicio {
+        return Err(AppError::field("dateTo", "deve ser posterior a dateFrom"));
+    }
+    if (fim - inicio).num_days() > 3660 {
+        return Err(AppError::field("dateTo", "intervalo máximo de 10 anos"));
+    }
+    let base = saldo_em(conn, &conta, inicio - Duration::days(1))?;
+    let (mut saldo, mut projetado) = (base.saldo, base.saldo_projetado);
+
+    // Movimento diário do período numa única consulta
+    let mut diario = std::collections::HashMap::new();
+    let mut stmt = conn.prepare(
+        "SELECT substr(data, 1, 10),
+            COALESCE(SUM(CASE WHEN status = 'pago' THEN CASE type WHEN 'entrada' THEN value ELSE -value END END), 0),
+            COALESCE(SUM(CASE WHEN status = 'previsto' THEN CASE type WHEN 'entrada' THEN value ELSE -value END END), 0)
+         FROM transacoes
+         WHERE deleted = 0 AND account = ?1 AND data >= ?2 AND data < ?3 AND data >= COALESCE(?4, '')
+         GROUP BY 1",
+    )?;
+    let rows = stmt.query_map(
+        params![
+            conta.nome,
+            inicio.format("%Y-%m-%d").to_string(),
+            (fim + Duration::days(1)).format("%Y-%m-%d").to_string(),
+            conta.data_saldo_inicial,
+        ],
+        |r| Ok((r.get::<_, String>(0)?, r.get::<_, Money>(1)?, r.get::<_, Money>(2)?)),
+    )?;
+    for r in rows {
+        let (dia, pago, previsto) = r?;
+        diario.insert(dia, (pago, previsto));
+    }
+
+    let mut out = vec![];
+    let mut dia = inicio;
+    while dia <= fim {
+        let chave = dia.format("%Y-%m-%d").to_string();
+        if let Some((pago, previsto)) = diario.get(&chave) {
+            saldo += *pago;
+            projetado += *pago + *previsto;
+        }
+        let fecha_ponto = match granularidade {
+            Granularidade::Diaria => true,
+            Granularidade::Mensal => dia == last_day_of_month(dia) || dia == fim,
+        };
+        if fecha_ponto {
+            out.push(PontoSaldo { data: chave, saldo, saldo_projetado: projetado });
+        }
+        dia += Duration::days(1);
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{self, Escrita};
+    use crate::models::Transacao;
+
+    fn inserir(conn: &Connection, id: &str, data: &str, centavos: i64, tipo: &str, status: &str, conta: &str) {
+        let tx: Transacao = serde_json::from_value(serde_json::json!({
+            "id": id, "date": data, "value": centavos, "type": tipo, "status": status, "account": conta
+        }))
+        .unwrap();
+        db::write_transacao(conn, &tx, Escrita::Replicada).unwrap();
+    }
+
+    #[test]
+    fn serie_parte_do_saldo_inicial_e_ignora_o_anterior_a_ele() {
+        let conn = Connection::open_in_memory().unwrap();
+        db::migrate(&conn).unwrap();
+        let conta = Conta {
+            nome: "Banco".to_string(),
+            tipo: TipoConta::Corrente,
+            saldo_inicial: Money::from_centavos(100_000),
+            data_saldo_inicial: Some("2026-03-01".to_string()),
+            ativo: true,
+            updated_at: None,
+        };
+        put_conta(&conn, &conta, "2026-03-01T00:00:00.000Z").unwrap();
+        inserir(&conn, "t1", "2026-02-20", 5000, "entrada", "pago", "Banco");
+        inserir(&conn, "t2", "2026-03-02", 2500, "saida", "pago", "Banco");
+        inserir(&conn, "t3", "2026-03-03", 1000, "entrada", "previsto", "Banco");
+        inserir(&conn, "t4", "2026-03-03", 999, "saida", "pago", "Outra");
+        let serie = serie_saldo(&conn, "Banco", "2026-03-01", "2026-03-03", Granularidade::Diaria).unwrap();
+        let pontos: Vec<(i64, i64)> = serie.iter().map(|p| (p.saldo.centavos(), p.saldo_projetado.centavos())).collect();
+        assert_eq!(pontos, [(100_000, 100_000), (97_500, 97_500), (97_500, 98_500)]);
+        let saldo = saldo_conta(&conn, "Banco", Some("2026-03-03")).unwrap();
+        assert_eq!((saldo.saldo, saldo.saldo_projetado), (Money::from_centavos(97_500), Money::from_centavos(98_500)));
+    }
+}
@@ -0,0 +1,276 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use rusqlite::{Connection, OptionalExtension};
+use serde_json::Value;
+
+use crate::error::AppError;
+
+const SERVICO: &str = "com.vertexads.financeiro";
+const TOKEN_KEY: &str = "authToken";
+const CHAVE_BANCO_KEY: &str = "dbKey";
+const CHAVE_ANTERIOR_KEY: &str = "dbKeyAnterior";
+const ARQUIVO_DADOS: &str = "credenciais.bin";
+const ARQUIVO_CHAVE: &str = "credenciais.chave";
+const NONCE_LEN: usize = 12;
+// Token que vence dentro dessa margem já conta como vencido: o sync não chega a
+// mandar uma requisição que voltaria 401.
+const MARGEM_EXPIRACAO_SECS: i64 = 60;
+
+/// Onde ficam os segredos: no chaveiro do sistema (Credential Manager, Keychain,
+/// Secret Service) ou, onde não há chaveiro, num arquivo cifrado na pasta de dados.
+/// A chave do arquivo fica ao lado dele, então o arquivo só protege contra quem
+/// copia o `vertexads.db`; o chaveiro protege também contra quem copia a pasta.
+enum Cofre {
+    #[cfg(any(target_os = "windows", target_os = "macos", target_os = "linux"))]
+    Chaveiro,
+    Arquivo(PathBuf),
+}
+
+static COFRE: OnceLock<Cofre> = OnceLock::new();
+// O que já foi lido ou gravado, para o sync não consultar o chaveiro a cada ciclo
+static CACHE: Mutex<BTreeMap<String, Option<String>>> = Mutex::new(BTreeMap::new());
+
+/// Escolhe o cofre na inicialização: o chaveiro, se responder, senão o arquivo
+/// cifrado em `dir`.
+pub fn iniciar(dir: &Path) {
+    let _ = COFRE.set(escolher(dir));
+}
+
+fn escolher(dir: &Path) -> Cofre {
+    #[cfg(any(target_os = "windows", target_os = "macos", target_os = "linux"))]
+    match keyring::Entry::new(SERVICO, TOKEN_KEY).and_then(|e| e.get_password()) {
+        Ok(_) | Err(keyring::Error::NoEntry) => return Cofre::Chaveiro,
+        Err(e) => eprintln!("[Credenciais] chaveiro indisponível ({}), usando arquivo cifrado", e),
+    }
+    Cofre::Arquivo(dir.to_path_buf())
+}
+
+fn cofre() -> Result<&'static Cofre, AppError> {
+    COFRE.get().ok_or_else(|| AppError::Internal("cofre de credenciais não iniciado".to_string()))
+}
+
+#[cfg(any(target_os = "windows", target_os = "macos", target_os = "linux"))]
+fn erro_chaveiro(e: keyring::Error) -> AppError {
+    AppError::Internal(format!("chaveiro do sistema: {}", e))
+}
+
+fn erro_arquivo(e: impl std::fmt::Display) -> AppError {
+    AppError::Internal(format!("arquivo de credenciais: {}", e))
+}
+
+// Chave do arquivo cifrado, criada na primeira gravação e legível só pelo usuário.
+fn chave_arquivo(dir: &Path, criar: bool) -> Result<Option<Key>, AppError> {
+    let path = dir.join(ARQUIVO_CHAVE);
+    match std::fs::read(&path) {
+        Ok(bytes) if bytes.len() == 32 => return Ok(Some(*Key::from_slice(&bytes))),
+        Ok(_) => eprintln!("[Credenciais] chave do arquivo inválida, gerando outra"),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => return Err(erro_arquivo(e)),
+    }
+    if !criar {
+        return Ok(None);
+    }
+    let key = ChaCha20Poly1305::generate_key(&mut OsRng);
+    let mut opts = std::fs::OpenOptions::new();
+    opts.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut opts, 0o600);
+    std::io::Write::write_all(&mut opts.open(&path).map_err(erro_arquivo)?, &key).map_err(erro_arquivo)?;
+    Ok(Some(key))
+}
+
+// Conteúdo do arquivo: nonce seguido do JSON `{chave: valor}` cifrado. Arquivo que
+// não abre com a chave atual é tratado como vazio (o usuário só faz login de novo).
+fn ler_arquivo(dir: &Path) -> Result<BTreeMap<String, String>, AppError> {
+    let dados = match std::fs::read(dir.join(ARQUIVO_DADOS)) {
+        Ok(d) if d.len() > NONCE_LEN => d,
+        Ok(_) => return Ok(BTreeMap::new()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(BTreeMap::new()),
+        Err(e) => return Err(erro_arquivo(e)),
+    };
+    let Some(key) = chave_arquivo(dir, false)? else { return Ok(BTreeMap::new()) };
+    let (nonce, cifrado) = dados.split_at(NONCE_LEN);
+    let aberto = ChaCha20Poly1305::new(&key)
+        .decrypt(Nonce::from_slice(nonce), cifrado)
+        .ok()
+        .and_then(|texto| serde_json::from_slice(&texto).ok());
+    if aberto.is_none() {
+        eprintln!("[Credenciais] arquivo de credenciais ilegível, descartado");
+    }
+    Ok(aberto.unwrap_or_default())
+}
+
+fn gravar_arquivo(dir: &Path, valores: &BTreeMap<String, String>) -> Result<(), AppError> {
+    let key = chave_arquivo(dir, true)?.ok_or_else(|| erro_arquivo("sem chave"))?;
+    let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let texto = serde_json::to_vec(valores).map_err(erro_arquivo)?;
+    let cifrado = ChaCha20Poly1305::new(&key).encrypt(&nonce, texto.as_slice()).map_err(erro_arquivo)?;
+    // Grava ao lado e renomeia, para uma queda no meio não corromper o arquivo
+    let tmp = dir.join(format!("{}.tmp", ARQUIVO_DADOS));
+    std::fs::write(&tmp, [nonce.as_slice(), &cifrado].concat()).map_err(erro_arquivo)?;
+    std::fs::rename(&tmp, dir.join(ARQUIVO_DADOS)).map_err(erro_arquivo)
+}
+
+impl Cofre {
+    fn ler(&self, chave: &str) -> Result<Option<String>, AppError> {
+        match self {
+            #[cfg(any(target_os = "windows", target_os = "macos", target_os = "linux"))]
+            Cofre::Chaveiro => match keyring::Entry::new(SERVICO, chave).and_then(|e| e.get_password()) {
+                Ok(v) => Ok(Some(v)),
+                Err(keyring::Error::NoEntry) => Ok(None),
+                Err(e) => Err(erro_chaveiro(e)),
+            },
+            Cofre::Arquivo(dir) => Ok(ler_arquivo(dir)?.remove(chave)),
+        }
+    }
+
+    fn gravar(&self, chave: &str, valor: Option<&str>) -> Result<(), AppError> {
+        match self {
+            #[cfg(any(target_os = "windows", target_os = "macos", target_os = "linux"))]
+            Cofre::Chaveiro => {
+                let entrada = keyring::Entry::new(SERVICO, chave).map_err(erro_chaveiro)?;
+                match valor {
+                    Some(v) => entrada.set_password(v).map_err(erro_chaveiro),
+                    None => match entrada.delete_credential() {
+                        Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+                        Err(e) => Err(erro_chaveiro(e)),
+                    },
+                }
+            }
+            Cofre::Arquivo(dir) => {
+                let mut valores = ler_arquivo(dir)?;
+                match valor {
+                    Some(v) => valores.insert(chave.to_string(), v.to_string()),
+                    None => valores.remove(chave),
+                };
+                gravar_arquivo(dir, &valores)
+            }
+        }
+    }
+}
+
+fn ler(chave: &str) -> Result<Option<String>, AppError> {
+    if let Some(v) = CACHE.lock()?.get(chave) {
+        return Ok(v.clone());
+    }
+    let v = cofre()?.ler(chave)?;
+    CACHE.lock()?.insert(chave.to_string(), v.clone());
+    Ok(v)
+}
+
+fn gravar(chave: &str, valor: Option<&str>) -> Result<(), AppError> {
+    cofre()?.gravar(chave, valor)?;
+    CACHE.lock()?.insert(chave.to_string(), valor.map(String::from));
+    Ok(())
+}
+
+// Leitura para quem só quer saber se há valor: erro do cofre vai para o log.
+fn ler_ou_nada(chave: &str) -> Option<String> {
+    match ler(chave) {
+        Ok(v) => v.filter(|v| !v.is_empty()),
+        Err(e) => {
+            eprintln!("[Credenciais] {}", e);
+            None
+        }
+    }
+}
+
+/// Token de login guardado, se houver.
+pub fn token() -> Option<String> {
+    ler_ou_nada(TOKEN_KEY)
+}
+
+/// Guarda o token de login; `None` ou vazio encerra a sessão.
+pub fn set_token(token: Option<&str>) -> Result<(), AppError> {
+    gravar(TOKEN_KEY, token.filter(|t| !t.is_empty()))
+}
+
+/// Há chaveiro do sistema. Sem ele, a chave do banco não teria onde ficar fora
+/// da pasta de dados, e o banco só pode ser cifrado com senha.
+pub fn tem_chaveiro() -> bool {
+    COFRE.get().is_some_and(|c| !matches!(c, Cofre::Arquivo(_)))
+}
+
+/// Chave do banco cifrado (hex), quando ele abre pelo chaveiro.
+pub fn chave_banco() -> Option<String> {
+    ler_ou_nada(CHAVE_BANCO_KEY)
+}
+
+pub fn set_chave_banco(chave: Option<&str>) -> Result<(), AppError> {
+    gravar(CHAVE_BANCO_KEY, chave)
+}
+
+/// Chave que abria o banco antes de uma troca de proteção ainda não concluída.
+pub fn chave_banco_anterior() -> Option<String> {
+    ler_ou_nada(CHAVE_ANTERIOR_KEY)
+}
+
+pub fn set_chave_banco_anterior(chave: Option<&str>) -> Result<(), AppError> {
+    gravar(CHAVE_ANTERIOR_KEY, chave)
+}
+
+/// Chave aleatória de 256 bits para o banco, em hex.
+pub fn nova_chave_banco() -> String {
+    ChaCha20Poly1305::generate_key(&mut OsRng).iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// `exp` do payload do JWT, em segundos desde a época. A assinatura não é
+/// verificada: serve só para saber quando pedir login de novo.
+pub fn expiracao(token: &str) -> Option<i64> {
+    let payload = token.split('.').nth(1)?;
+    let bytes = URL_SAFE_NO_PAD.decode(payload.trim_end_matches('=')).ok()?;
+    serde_json::from_slice::<Value>(&bytes).ok()?.get("exp")?.as_i64()
+}
+
+pub fn sessao_expirada(token: &str) -> bool {
+    expiracao(token).is_some_and(|exp| exp - MARGEM_EXPIRACAO_SECS <= chrono::Utc::now().timestamp())
+}
+
+/// Token para uma chamada ao servidor. Vencido, vira `Unauthorized` sem ir à rede,
+/// e o frontend manda para o login em vez de mostrar falha de sync.
+pub fn token_valido() -> Result<Option<String>, AppError> {
+    let token = token();
+    if token.as_deref().is_some_and(sessao_expirada) {
+        return Err(AppError::Unauthorized("Sessão expirada. Faça login novamente.".to_string()));
+    }
+    Ok(token)
+}
+
+/// Move para o cofre o token que versões anteriores guardavam em texto puro na
+/// tabela config e apaga o valor do banco, sobrescrevendo a página em vez de só
+/// liberá-la. Se o cofre falhar, o token fica onde estava e a próxima abertura
+/// tenta de novo.
+pub fn migrar_de_config(conn: &Connection) -> Result<(), AppError> {
+    let antigo: Option<String> =
+        conn.query_row("SELECT value FROM config WHERE key = ?1", [TOKEN_KEY], |r| r.get(0)).optional()?;
+    let Some(antigo) = antigo else { return Ok(()) };
+    if !antigo.is_empty() && token().is_none() {
+        set_token(Some(&antigo))?;
+    }
+    apagar_de_config(conn)
+}
+
+/// Apaga o token legado da tabela config sem passar pelo cofre. Serve para as
+/// cópias de backup, que podem ser tiradas antes de `migrar_de_config` rodar.
+pub fn apagar_de_config(conn: &Connection) -> Result<(), AppError> {
+    let tem_config: bool = conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'config')",
+        [],
+        |r| r.get(0),
+    )?;
+    if !tem_config {
+        return Ok(());
+    }
+    conn.pragma_update(None, "secure_delete", true)?;
+    conn.execute("DELETE FROM config WHERE key = ?1", [TOKEN_KEY])?;
+    conn.pragma_update(None, "secure_delete", false)?;
+    // A página antiga ainda está no WAL até o checkpoint
+    conn.query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |_| Ok(()))?;
+    Ok(())
+}
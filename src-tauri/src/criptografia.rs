@@ -0,0 +1,168 @@
+use std::io::Read;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::credenciais;
+use crate::error::AppError;
+use crate::pool::{ChaveBanco, Pool};
+
+const CABECALHO_SQLITE: &[u8; 16] = b"SQLite format 3\0";
+const SENHA_MINIMA: usize = 8;
+
+/// Como o banco está protegido no disco. `Chaveiro`: chave aleatória guardada no
+/// chaveiro do sistema, o app abre sem perguntar. `Senha`: chave derivada de uma
+/// senha pedida a cada abertura.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ModoCifra {
+    Nenhuma,
+    Chaveiro,
+    Senha,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EstadoCifra {
+    pub modo: ModoCifra,
+    /// Banco cifrado com senha e ainda fechado nesta sessão.
+    pub aguardando_senha: bool,
+    pub chaveiro_disponivel: bool,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Protecao {
+    pub modo: ModoCifra,
+    pub senha: Option<String>,
+}
+
+/// O arquivo existe e não começa com o cabeçalho do SQLite, ou seja, está cifrado.
+pub fn cifrado(path: &Path) -> bool {
+    let mut cabecalho = [0u8; 16];
+    match std::fs::File::open(path).and_then(|mut f| f.read_exact(&mut cabecalho)) {
+        Ok(()) => &cabecalho != CABECALHO_SQLITE,
+        Err(_) => false,
+    }
+}
+
+/// Chave para abrir o banco sem perguntar nada: `None` para banco sem cifra, a do
+/// chaveiro se houver, ou `DbLocked` quando só a senha do usuário abre.
+pub fn chave_inicial(path: &Path) -> Result<Option<ChaveBanco>, AppError> {
+    if !cifrado(path) {
+        return Ok(None);
+    }
+    match credenciais::chave_banco() {
+        Some(hex) => Ok(Some(ChaveBanco::Bruta(hex))),
+        None => Err(AppError::DbLocked("banco protegido por senha: informe a senha para abrir".to_string())),
+    }
+}
+
+/// Chave do banco vazio que entra no lugar de um que não abriu, para recriar não
+/// tirar a proteção: a do chaveiro, se houver, ou a `senha` informada (pode ser
+/// nova; a antiga pode ter sido esquecida). Sem ela, um banco atual cifrado só com
+/// senha é recusado em vez de virar um banco sem cifra.
+pub fn chave_recriacao(path: &Path, senha: Option<&str>) -> Result<Option<ChaveBanco>, AppError> {
+    if let Some(hex) = credenciais::chave_banco() {
+        return Ok(Some(ChaveBanco::Bruta(hex)));
+    }
+    match senha {
+        Some(s) => Ok(Some(ChaveBanco::Senha(validar_senha(Some(s))?))),
+        None if cifrado(path) => Err(AppError::field(
+            "senha",
+            "o banco atual é protegido por senha: informe uma senha para o banco novo",
+        )),
+        None => Ok(None),
+    }
+}
+
+pub fn estado(path: &Path, aguardando_senha: bool) -> EstadoCifra {
+    let modo = if !cifrado(path) {
+        ModoCifra::Nenhuma
+    } else if credenciais::chave_banco().is_some() {
+        ModoCifra::Chaveiro
+    } else {
+        ModoCifra::Senha
+    };
+    EstadoCifra { modo, aguardando_senha, chaveiro_disponivel: credenciais::tem_chaveiro() }
+}
+
+fn validar_senha(senha: Option<&str>) -> Result<String, AppError> {
+    match senha {
+        Some(s) if s.chars().count() >= SENHA_MINIMA => Ok(s.to_string()),
+        _ => Err(AppError::field("senha", format!("mínimo de {} caracteres", SENHA_MINIMA))),
+    }
+}
+
+/// Aplica a proteção escolhida: cifra um banco aberto (a migração única do
+/// arquivo sem cifra), troca chaveiro por senha e vice-versa, ou tira a cifra.
+/// Durante a troca o chaveiro guarda a chave nova e, numa segunda entrada, a
+/// antiga: se o app cair no meio, `abrir` tenta as duas.
+pub fn proteger(pool: &Pool, protecao: &Protecao) -> Result<(), AppError> {
+    let anterior = credenciais::chave_banco();
+    let nova = match protecao.modo {
+        ModoCifra::Nenhuma => None,
+        ModoCifra::Chaveiro => {
+            if !credenciais::tem_chaveiro() {
+                return Err(AppError::field("modo", "chaveiro do sistema indisponível; use uma senha"));
+            }
+            Some(ChaveBanco::Bruta(credenciais::nova_chave_banco()))
+        }
+        ModoCifra::Senha => Some(ChaveBanco::Senha(validar_senha(protecao.senha.as_deref())?)),
+    };
+    let hex = match &nova {
+        Some(ChaveBanco::Bruta(hex)) => Some(hex.clone()),
+        _ => None,
+    };
+    credenciais::set_chave_banco_anterior(anterior.as_deref())?;
+    if let Err(e) = credenciais::set_chave_banco(hex.as_deref()).and_then(|_| pool.recifrar(nova)) {
+        let _ = credenciais::set_chave_banco(anterior.as_deref());
+        let _ = credenciais::set_chave_banco_anterior(None);
+        return Err(e);
+    }
+    let _ = credenciais::set_chave_banco_anterior(None);
+    Ok(())
+}
+
+/// Abre o banco com a chave do chaveiro. Se a chave não serve e sobrou a de uma
+/// troca interrompida, o arquivo ainda é o de antes: com ela abrindo, ela volta a
+/// ser a do chaveiro. Na falha devolve a chave tentada com o erro.
+pub fn abrir(path: &Path) -> Result<Pool, (Option<ChaveBanco>, AppError)> {
+    let (chave, erro) = match chave_inicial(path) {
+        Ok(chave) => match Pool::open(path, chave.clone()) {
+            Ok(p) => {
+                // Troca que caiu depois de trocar o arquivo: a anterior já não serve
+                if credenciais::chave_banco_anterior().is_some() {
+                    let _ = credenciais::set_chave_banco_anterior(None);
+                }
+                return Ok(p);
+            }
+            Err(e) => (chave, e),
+        },
+        Err(e) => (None, e),
+    };
+    if matches!(erro, AppError::WrongKey(_) | AppError::DbLocked(_)) {
+        if let Some(hex) = credenciais::chave_banco_anterior() {
+            if let Ok(p) = Pool::open(path, Some(ChaveBanco::Bruta(hex.clone()))) {
+                eprintln!("[Cifra] troca de chave interrompida; voltando à chave anterior");
+                // Se o chaveiro falhar agora, a anterior fica e a próxima abertura tenta de novo
+                match credenciais::set_chave_banco(Some(&hex)) {
+                    Ok(()) => {
+                        let _ = credenciais::set_chave_banco_anterior(None);
+                    }
+                    Err(e) => eprintln!("[Cifra] {}", e),
+                }
+                return Ok(p);
+            }
+        }
+    }
+    Err((chave, erro))
+}
+
+/// Troca a senha de um banco protegido por senha, conferindo a atual.
+pub fn alterar_senha(pool: &Pool, atual: &str, nova: &str) -> Result<(), AppError> {
+    if !pool.chave_confere(&ChaveBanco::Senha(atual.to_string()))? {
+        return Err(AppError::WrongKey("senha atual incorreta".to_string()));
+    }
+    proteger(pool, &Protecao { modo: ModoCifra::Senha, senha: Some(nova.to_string()) })
+}
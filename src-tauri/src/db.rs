@@ -1,378 +1,785 @@
 use rusqlite::{params, Connection};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
 use serde_json::Value;
 
-// Embutido no build. Padrão localhost:3001 (cada máquina tem app + servidor local)
-const AUTH_TOKEN_KEY: &str = "authToken";
+use crate::conflitos;
+use crate::contas;
+use crate::credenciais;
+use crate::error::AppError;
+use crate::migrations::{self, MigrationError};
+use crate::models::{Config, Entidade, Recorrencia, TipoConta, Transacao, ValidationErrors, CONFIG_LIST_KEYS};
+use crate::money::Money;
+use crate::recorrencias;
+use crate::servidores;
+use crate::tombstones::{self, Tombstone};
 
-fn api_url() -> String {
-    option_env!("TAURI_APP_CLOUD_API_URL")
-        .map(String::from)
-        .or_else(|| std::env::var("TAURI_APP_CLOUD_API_URL").ok())
-        .unwrap_or_else(|| "http://localhost:3001".to_string())
+/// Há servidor configurado e login feito; sem isso o sync em segundo plano não roda.
+pub fn sync_habilitado(conn: &Connection) -> bool {
+    !servidores::url_ativa(conn).is_empty() && credenciais::token().is_some()
 }
 
-fn get_auth_token(conn: &Connection) -> Option<String> {
-    conn.query_row("SELECT value FROM config WHERE key = ?1", [AUTH_TOKEN_KEY], |r| r.get(0))
-        .ok()
-        .and_then(|s: String| if s.is_empty() { None } else { Some(s) })
-}
-
-pub fn migrate(conn: &Connection) -> Result<(), rusqlite::Error> {
-    conn.execute_batch(
-        r#"
-        CREATE TABLE IF NOT EXISTS transacoes (
-            id TEXT PRIMARY KEY,
-            data TEXT NOT NULL,
-            description TEXT,
-            client TEXT,
-            value REAL NOT NULL,
-            type TEXT NOT NULL,
-            contexto TEXT,
-            contraparte TEXT,
-            category TEXT,
-            account TEXT,
-            metodo_pagamento TEXT,
-            status TEXT,
-            deleted INTEGER NOT NULL DEFAULT 0,
-            recorrencia_id TEXT,
-            updated_at TEXT
-        );
-        CREATE TABLE IF NOT EXISTS recorrentes (
-            id TEXT PRIMARY KEY,
-            titulo TEXT,
-            valor REAL,
-            tipo TEXT,
-            categoria TEXT,
-            conta TEXT,
-            metodo_pagamento TEXT,
-            dia_vencimento INTEGER,
-            ativo INTEGER,
-            updated_at TEXT
-        );
-        CREATE TABLE IF NOT EXISTS config (
-            key TEXT PRIMARY KEY,
-            value TEXT,
-            updated_at TEXT
-        );
-        "#,
+pub fn migrate(conn: &Connection) -> Result<(), MigrationError> {
+    migrations::run(conn)?;
+    Ok(())
+}
+
+pub const TRANSACAO_COLUMNS: &str = "id, data, description, client, value, type, contexto, contraparte, category, account, metodo_pagamento, status, deleted, recorrencia_id, updated_at, origem_device";
+
+const RECORRENCIA_COLUMNS: &str = "id, titulo, valor, tipo, categoria, conta, metodo_pagamento, dia_vencimento, ativo, frequencia, recorrente, quantidade_meses, data_inicio, cliente_fornecedor, contexto, updated_at, origem_device";
+
+// Entidade do change_log para as listas de config; o id é a chave.
+const CONFIG_ENTIDADE: &str = "config";
+
+/// Chave de config com o `cursor` do último pull, emitido pelo servidor. É o `since`
+/// do próximo: o relógio deste aparelho perderia edições feitas offline em outro.
+/// `lastSyncedAt` fica só para mostrar quando foi o último sync.
+pub const CURSOR_SYNC_KEY: &str = "cursorSync";
+
+/// Registra no change_log que o registro mudou e precisa ir no próximo push.
+/// O change_log (com as lápides ainda não enviadas) é o outbox do sync: é gravado
+/// na mesma transação da escrita local, então uma edição salva sobrevive a queda,
+/// falta de energia ou fechar o app offline, e sai no próximo push que der certo.
+pub fn mark_changed(conn: &Connection, entidade: &str, id: &str) -> rusqlite::Result<()> {
+    conn.execute(
+        "INSERT OR REPLACE INTO change_log (entidade, id, seq)
+         VALUES (?1, ?2, (SELECT COALESCE(MAX(seq), 0) + 1 FROM change_log))",
+        params![entidade, id],
     )?;
     Ok(())
 }
 
-fn row_to_json(row: &rusqlite::Row) -> Result<Value, rusqlite::Error> {
-    let mut map = serde_json::Map::new();
-    for (i, name) in row.as_ref().column_names().iter().enumerate() {
-        let v = row.get::<_, rusqlite::types::Value>(i)?;
-        let j = match v {
-            rusqlite::types::Value::Null => Value::Null,
-            rusqlite::types::Value::Integer(n) => Value::Number(serde_json::Number::from(n)),
-            rusqlite::types::Value::Real(f) => serde_json::Number::from_f64(f).map(Value::Number).unwrap_or(Value::Null),
-            rusqlite::types::Value::Text(s) => Value::String(s),
-            rusqlite::types::Value::Blob(_) => Value::Null,
-        };
-        map.insert(name.to_string(), j);
+/// Põe a base inteira no outbox, como a migração do change_log fez, e marca as
+/// lápides como não enviadas: o próximo push leva tudo (ex: para um servidor novo).
+pub fn mark_all_changed(conn: &Connection) -> rusqlite::Result<()> {
+    let seq: i64 = conn.query_row("SELECT COALESCE(MAX(seq), 0) + 1 FROM change_log", [], |r| r.get(0))?;
+    conn.execute(
+        "INSERT OR REPLACE INTO change_log (entidade, id, seq) SELECT 'transacao', id, ?1 FROM transacoes",
+        [seq],
+    )?;
+    conn.execute(
+        "INSERT OR REPLACE INTO change_log (entidade, id, seq) SELECT 'recorrencia', id, ?1 FROM recorrentes",
+        [seq],
+    )?;
+    for key in CONFIG_LIST_KEYS {
+        conn.execute(
+            "INSERT OR REPLACE INTO change_log (entidade, id, seq) SELECT ?1, key, ?2 FROM config WHERE key = ?3",
+            params![CONFIG_ENTIDADE, seq, key],
+        )?;
+    }
+    conn.execute("UPDATE tombstones SET pushed = 0", [])?;
+    Ok(())
+}
+
+// Alterações ainda não confirmadas pelo servidor, com o maior seq incluído.
+struct PendingChanges {
+    max_seq: i64,
+    transacoes: Vec<Transacao>,
+    recorrentes: Vec<Recorrencia>,
+    config: serde_json::Map<String, Value>,
+}
+
+impl PendingChanges {
+    fn is_empty(&self) -> bool {
+        self.transacoes.is_empty() && self.recorrentes.is_empty() && self.config.is_empty()
+    }
+}
+
+/// Quantos registros e exclusões aguardam o push (fora os retidos por conflito).
+pub fn pending_count(conn: &Connection) -> rusqlite::Result<i64> {
+    conn.query_row(
+        "SELECT (SELECT COUNT(*) FROM change_log l
+                 WHERE NOT EXISTS (SELECT 1 FROM conflitos c WHERE c.entidade = l.entidade AND c.id = l.id))
+              + (SELECT COUNT(*) FROM tombstones WHERE pushed = 0)",
+        [],
+        |r| r.get(0),
+    )
+}
+
+pub fn is_pending(conn: &Connection, entidade: &str, id: &str) -> rusqlite::Result<bool> {
+    conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM change_log WHERE entidade = ?1 AND id = ?2)",
+        params![entidade, id],
+        |r| r.get(0),
+    )
+}
+
+pub fn clear_pending(conn: &Connection, entidade: &str, id: &str) -> rusqlite::Result<()> {
+    conn.execute("DELETE FROM change_log WHERE entidade = ?1 AND id = ?2", params![entidade, id])?;
+    Ok(())
+}
+
+fn pending_changes(conn: &Connection) -> Result<PendingChanges, AppError> {
+    let max_seq = conn.query_row("SELECT COALESCE(MAX(seq), 0) FROM change_log", [], |r| r.get(0))?;
+    let sql = format!(
+        "SELECT {} FROM transacoes WHERE id IN (SELECT id FROM change_log WHERE entidade = 'transacao')
+         AND id NOT IN (SELECT id FROM conflitos WHERE entidade = 'transacao')",
+        TRANSACAO_COLUMNS
+    );
+    let transacoes = conn.prepare(&sql)?.query_map([], transacao_from_row)?.collect::<Result<Vec<_>, _>>()?;
+    let sql = format!(
+        "SELECT {} FROM recorrentes WHERE id IN (SELECT id FROM change_log WHERE entidade = 'recorrencia')
+         AND id NOT IN (SELECT id FROM conflitos WHERE entidade = 'recorrencia')",
+        RECORRENCIA_COLUMNS
+    );
+    let recorrentes = conn.prepare(&sql)?.query_map([], recorrencia_from_row)?.collect::<Result<Vec<_>, _>>()?;
+    let mut config = serde_json::Map::new();
+    let mut stmt = conn.prepare(
+        "SELECT c.key, c.value FROM config c JOIN change_log l ON l.entidade = 'config' AND l.id = c.key",
+    )?;
+    for row in stmt.query_map([], |r| Ok((r.get::<_, String>(0)?, r.get::<_, String>(1)?)))? {
+        let (key, value) = row?;
+        // Horizonte marcado por versões anteriores não sai mais
+        if !CONFIG_LIST_KEYS.contains(&key.as_str()) {
+            continue;
+        }
+        if let Ok(v) = serde_json::from_str(&value) {
+            config.insert(key, v);
+        }
+    }
+    Ok(PendingChanges { max_seq, transacoes, recorrentes, config })
+}
+
+/// Timestamp de `updated_at`/`lastSyncedAt`: ISO 8601 em UTC com milissegundos,
+/// o mesmo formato do servidor, para a comparação de versões no merge.
+pub fn now_iso() -> String {
+    chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true)
+}
+
+/// Instante de um `updated_at` em milissegundos. Aceita ISO 8601 e o formato antigo
+/// (segundos desde a época, como texto).
+pub fn version_millis(updated_at: &str) -> Option<i64> {
+    if !updated_at.is_empty() && updated_at.bytes().all(|b| b.is_ascii_digit()) {
+        return updated_at.parse::<i64>().ok().map(|s| s * 1000);
+    }
+    chrono::DateTime::parse_from_rfc3339(updated_at).ok().map(|d| d.timestamp_millis())
+}
+
+pub fn transacao_from_row(row: &rusqlite::Row) -> Result<Transacao, rusqlite::Error> {
+    Ok(Transacao {
+        id: row.get(0)?,
+        date: row.get(1)?,
+        description: row.get(2)?,
+        client: row.get(3)?,
+        value: row.get(4)?,
+        tipo: row.get(5)?,
+        contexto: row.get(6)?,
+        contraparte: row.get(7)?,
+        category: row.get(8)?,
+        account: row.get(9)?,
+        metodo_pagamento: row.get(10)?,
+        status: row.get(11)?,
+        deleted: row.get(12)?,
+        recorrencia_id: row.get(13)?,
+        updated_at: row.get(14)?,
+        origem_device: row.get(15)?,
+    })
+}
+
+fn recorrencia_from_row(row: &rusqlite::Row) -> Result<Recorrencia, rusqlite::Error> {
+    Ok(Recorrencia {
+        id: row.get(0)?,
+        titulo: row.get(1)?,
+        valor: row.get(2)?,
+        tipo: row.get(3)?,
+        categoria: row.get(4)?,
+        conta: row.get(5)?,
+        metodo_pagamento: row.get(6)?,
+        dia_vencimento: row.get(7)?,
+        ativo: row.get(8)?,
+        frequencia: row.get(9)?,
+        recorrente: row.get(10)?,
+        quantidade_meses: row.get(11)?,
+        data_inicio: row.get(12)?,
+        cliente_fornecedor: row.get(13)?,
+        contexto: row.get(14)?,
+        updated_at: row.get(15)?,
+        origem_device: row.get(16)?,
+    })
+}
+
+/// Valida cada item do lote; os erros levam o índice no campo, ex: items[3].date
+fn validate_batch<T>(items: &[T], validate: impl Fn(&T) -> Result<(), ValidationErrors>) -> Result<(), AppError> {
+    let mut errors = ValidationErrors::default();
+    for (i, item) in items.iter().enumerate() {
+        if let Err(e) = validate(item) {
+            errors.0.extend(e.prefixed(&format!("items[{}]", i)).0);
+        }
     }
-    Ok(Value::Object(map))
+    Ok(errors.into_result()?)
 }
 
-pub fn get_all_transacoes(conn: &Connection) -> Result<Vec<Value>, String> {
-    let mut stmt = conn.prepare("SELECT id, data as date, description, client, value, type, contexto, contraparte, category, account, metodo_pagamento as metodoPagamento, status, deleted, recorrencia_id as recorrenciaId, updated_at as updatedAt FROM transacoes ORDER BY data DESC").map_err(|e| e.to_string())?;
-    let rows = stmt.query_map([], |row| row_to_json(row)).map_err(|e| e.to_string())?;
+pub fn get_all_transacoes(conn: &Connection) -> Result<Vec<Transacao>, AppError> {
+    let sql = format!("SELECT {} FROM transacoes ORDER BY data DESC", TRANSACAO_COLUMNS);
+    let mut stmt = conn.prepare(&sql)?;
+    let rows = stmt.query_map([], transacao_from_row)?;
     let mut out = vec![];
     for r in rows {
-        out.push(r.map_err(|e| e.to_string())?);
+        out.push(r?);
     }
     Ok(out)
 }
 
-pub fn delete_transacao(conn: &Connection, id: &str) -> Result<(), String> {
-    conn.execute("DELETE FROM transacoes WHERE id = ?1", [id])
-        .map_err(|e| e.to_string())?;
-    Ok(())
+pub fn get_transacao(conn: &Connection, id: &str) -> Result<Option<Transacao>, AppError> {
+    let sql = format!("SELECT {} FROM transacoes WHERE id = ?1", TRANSACAO_COLUMNS);
+    let mut stmt = conn.prepare(&sql)?;
+    let mut rows = stmt.query_map([id], transacao_from_row)?;
+    Ok(rows.next().transpose()?)
 }
 
-pub fn put_transacao(conn: &Connection, tx: Value) -> Result<(), String> {
-    let obj = tx.as_object().ok_or("expected object")?;
-    let id = obj.get("id").and_then(|v| v.as_str()).unwrap_or("");
-    let date = obj.get("date").and_then(|v| v.as_str()).unwrap_or("");
-    let description = obj.get("description").and_then(|v| v.as_str());
-    let client = obj.get("client").and_then(|v| v.as_str());
-    let value = obj.get("value").and_then(|v| v.as_f64()).unwrap_or(0.0);
-    let tipo = obj.get("type").and_then(|v| v.as_str()).unwrap_or("saida");
-    let contexto = obj.get("contexto").and_then(|v| v.as_str());
-    let contraparte = obj.get("contraparte").and_then(|v| v.as_str());
-    let category = obj.get("category").and_then(|v| v.as_str());
-    let account = obj.get("account").and_then(|v| v.as_str());
-    let metodo_pagamento = obj.get("metodoPagamento").and_then(|v| v.as_str());
-    let status = obj.get("status").and_then(|v| v.as_str());
-    let deleted = obj.get("deleted").and_then(|v| v.as_bool()).unwrap_or(false);
-    let recorrencia_id = obj.get("recorrenciaId").and_then(|v| v.as_str());
-    let updated_at = std::time::SystemTime::now()
-        .duration_since(std::time::UNIX_EPOCH)
-        .ok()
-        .map(|d| d.as_secs().to_string());
-    conn.execute(
-        r#"INSERT OR REPLACE INTO transacoes (id, data, description, client, value, type, contexto, contraparte, category, account, metodo_pagamento, status, deleted, recorrencia_id, updated_at)
-           VALUES (?1,?2,?3,?4,?5,?6,?7,?8,?9,?10,?11,?12,?13,?14,?15)"#,
-        params![id, date, description, client, value, tipo, contexto, contraparte, category, account, metodo_pagamento, status, if deleted { 1i32 } else { 0i32 }, recorrencia_id, updated_at],
+pub fn delete_transacao(conn: &Connection, id: &str) -> Result<(), AppError> {
+    tombstones::excluir(conn, Entidade::Transacao, id)
+}
+
+// Como um registro chega ao banco. `Local` é edição do usuário neste aparelho:
+// recebe updated_at de agora, este aparelho como origem e entra no change_log.
+// `Replicada` vem do servidor (pull/restore): mantém updatedAt e origem recebidos
+// e não volta no push. `Importada` vem de um arquivo exportado: mantém os
+// metadados, como a replicada, mas entra no change_log para chegar ao servidor.
+#[derive(Clone, Copy)]
+pub enum Escrita<'a> {
+    Local { updated_at: &'a str },
+    Replicada,
+    Importada,
+}
+
+// INSERT que, se o id já existe, só atualiza quando algum campo mudou. Na escrita
+// local os metadados não contam: regravar o mesmo registro (o frontend salva a
+// lista inteira) não mexe em updated_at nem entra no change_log.
+fn upsert_sql(table: &str, columns: &str, escrita: Escrita) -> String {
+    let cols: Vec<&str> = columns.split(", ").collect();
+    let placeholders: Vec<String> = (1..=cols.len()).map(|i| format!("?{}", i)).collect();
+    let comparadas: Vec<&str> = cols
+        .iter()
+        .copied()
+        .filter(|c| *c != "id" && (!matches!(escrita, Escrita::Local { .. }) || !["updated_at", "origem_device"].contains(c)))
+        .collect();
+    format!(
+        "INSERT INTO {table} ({columns}) VALUES ({}) ON CONFLICT (id) DO UPDATE SET {} WHERE ({}) IS NOT ({})",
+        placeholders.join(", "),
+        cols.iter().filter(|c| **c != "id").map(|c| format!("{c} = excluded.{c}")).collect::<Vec<_>>().join(", "),
+        comparadas.iter().map(|c| format!("{table}.{c}")).collect::<Vec<_>>().join(", "),
+        comparadas.iter().map(|c| format!("excluded.{c}")).collect::<Vec<_>>().join(", "),
     )
-    .map_err(|e| e.to_string())?;
+}
+
+// updated_at e origem gravados conforme o caminho de escrita.
+fn metadados(conn: &Connection, escrita: Escrita, updated_at: &Option<String>, origem: &Option<String>) -> rusqlite::Result<(String, Option<String>)> {
+    Ok(match escrita {
+        Escrita::Local { updated_at } => (updated_at.to_string(), Some(tombstones::device_id(conn)?)),
+        Escrita::Replicada | Escrita::Importada => (updated_at.clone().unwrap_or_else(now_iso), origem.clone()),
+    })
+}
+
+/// Grava a transação; devolve se algo mudou no banco.
+pub fn write_transacao(conn: &Connection, tx: &Transacao, escrita: Escrita) -> Result<bool, rusqlite::Error> {
+    let (updated_at, origem) = metadados(conn, escrita, &tx.updated_at, &tx.origem_device)?;
+    let changed = conn.execute(
+        &upsert_sql("transacoes", TRANSACAO_COLUMNS, escrita),
+        params![tx.id, tx.date, tx.description, tx.client, tx.value, tx.tipo, tx.contexto, tx.contraparte, tx.category, tx.account, tx.metodo_pagamento, tx.status, tx.deleted, tx.recorrencia_id, updated_at, origem],
+    )?;
+    if changed > 0 && !matches!(escrita, Escrita::Replicada) {
+        mark_changed(conn, Entidade::Transacao.as_str(), &tx.id)?;
+    }
+    if let Some(account) = &tx.account {
+        contas::ensure_conta(conn, account, TipoConta::Corrente)?;
+    }
+    Ok(changed > 0)
+}
+
+pub fn put_transacao(conn: &Connection, tx: &Transacao) -> Result<(), AppError> {
+    tx.validate()?;
+    write_transacao(conn, tx, Escrita::Local { updated_at: &now_iso() })?;
+    Ok(())
+}
+
+/// Grava o lote numa única transação; um item inválido rejeita o lote inteiro.
+pub fn put_transacoes(conn: &Connection, items: &[Transacao]) -> Result<(), AppError> {
+    validate_batch(items, Transacao::validate)?;
+    let updated_at = now_iso();
+    let db_tx = conn.unchecked_transaction()?;
+    for tx in items {
+        write_transacao(&db_tx, tx, Escrita::Local { updated_at: &updated_at })?;
+    }
+    db_tx.commit()?;
     Ok(())
 }
 
-pub fn get_all_recorrentes(conn: &Connection) -> Result<Vec<Value>, String> {
-    let mut stmt = conn.prepare("SELECT * FROM recorrentes").map_err(|e| e.to_string())?;
-    let rows = stmt.query_map([], |row| row_to_json(row)).map_err(|e| e.to_string())?;
+pub fn get_all_recorrentes(conn: &Connection) -> Result<Vec<Recorrencia>, AppError> {
+    let sql = format!("SELECT {} FROM recorrentes", RECORRENCIA_COLUMNS);
+    let mut stmt = conn.prepare(&sql)?;
+    let rows = stmt.query_map([], recorrencia_from_row)?;
     let mut out = vec![];
     for r in rows {
-        out.push(r.map_err(|e| e.to_string())?);
+        out.push(r?);
     }
     Ok(out)
 }
 
-pub fn put_recorrentes(conn: &Connection, items: Vec<Value>) -> Result<(), String> {
+pub fn get_recorrencia(conn: &Connection, id: &str) -> Result<Option<Recorrencia>, AppError> {
+    let sql = format!("SELECT {} FROM recorrentes WHERE id = ?1", RECORRENCIA_COLUMNS);
+    let mut stmt = conn.prepare(&sql)?;
+    let mut rows = stmt.query_map([id], recorrencia_from_row)?;
+    Ok(rows.next().transpose()?)
+}
+
+pub fn put_recorrentes(conn: &Connection, items: &[Recorrencia]) -> Result<(), AppError> {
+    validate_batch(items, Recorrencia::validate)?;
+    let updated_at = now_iso();
+    let db_tx = conn.unchecked_transaction()?;
     for r in items {
-        put_recorrencia(conn, r)?;
+        gravar_recorrencia_local(&db_tx, r, &updated_at)?;
     }
+    db_tx.commit()?;
     Ok(())
 }
 
-pub fn put_recorrencia(conn: &Connection, r: Value) -> Result<(), String> {
-    let obj = r.as_object().ok_or("expected object")?;
-    let id = obj.get("id").and_then(|v| v.as_str()).unwrap_or("");
-    let titulo = obj.get("titulo").and_then(|v| v.as_str());
-    let valor = obj.get("valor").and_then(|v| v.as_f64()).unwrap_or(0.0);
-    let tipo = obj.get("tipo").and_then(|v| v.as_str());
-    let categoria = obj.get("categoria").and_then(|v| v.as_str());
-    let conta = obj.get("conta").and_then(|v| v.as_str());
-    let metodo_pagamento = obj.get("metodoPagamento").and_then(|v| v.as_str());
-    let dia_vencimento = obj.get("diaVencimento").and_then(|v| v.as_i64());
-    let ativo = obj.get("ativo").and_then(|v| v.as_bool()).unwrap_or(true);
-    let updated_at = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).ok().map(|d| d.as_secs().to_string());
-    conn.execute(
-        r#"INSERT OR REPLACE INTO recorrentes (id, titulo, valor, tipo, categoria, conta, metodo_pagamento, dia_vencimento, ativo, updated_at) VALUES (?1,?2,?3,?4,?5,?6,?7,?8,?9,?10)"#,
-        params![id, titulo, valor, tipo, categoria, conta, metodo_pagamento, dia_vencimento, if ativo { 1i32 } else { 0i32 }, updated_at],
-    )
-    .map_err(|e| e.to_string())?;
+// Edição local: só mexe nos lançamentos previstos se mudou algo que eles seguem,
+// senão regravar a recorrência desfaria o que o usuário editou à mão neles.
+fn gravar_recorrencia_local(conn: &Connection, r: &Recorrencia, updated_at: &str) -> Result<(), AppError> {
+    let antiga = get_recorrencia(conn, &r.id)?;
+    let mudou = write_recorrencia(conn, r, Escrita::Local { updated_at })?;
+    if mudou && antiga.map_or(true, |a| recorrencias::altera_ocorrencias(&a, r)) {
+        recorrencias::propagar_alteracao(conn, r, updated_at)?;
+    }
+    Ok(())
+}
+
+/// Grava a recorrência; devolve se algo mudou no banco.
+pub fn write_recorrencia(conn: &Connection, r: &Recorrencia, escrita: Escrita) -> Result<bool, rusqlite::Error> {
+    let (updated_at, origem) = metadados(conn, escrita, &r.updated_at, &r.origem_device)?;
+    let changed = conn.execute(
+        &upsert_sql("recorrentes", RECORRENCIA_COLUMNS, escrita),
+        params![r.id, r.titulo, r.valor, r.tipo, r.categoria, r.conta, r.metodo_pagamento, r.dia_vencimento, r.ativo, r.frequencia, r.recorrente, r.quantidade_meses, r.data_inicio, r.cliente_fornecedor, r.contexto, updated_at, origem],
+    )?;
+    if changed > 0 && !matches!(escrita, Escrita::Replicada) {
+        mark_changed(conn, Entidade::Recorrencia.as_str(), &r.id)?;
+    }
+    Ok(changed > 0)
+}
+
+pub fn delete_recorrencia(conn: &Connection, id: &str) -> Result<(), AppError> {
+    tombstones::excluir(conn, Entidade::Recorrencia, id)
+}
+
+pub fn put_recorrencia(conn: &Connection, r: &Recorrencia) -> Result<(), AppError> {
+    r.validate()?;
+    let updated_at = now_iso();
+    let db_tx = conn.unchecked_transaction()?;
+    gravar_recorrencia_local(&db_tx, r, &updated_at)?;
+    db_tx.commit()?;
     Ok(())
 }
 
-pub fn get_config(conn: &Connection) -> Result<Value, String> {
-    let mut map = serde_json::Map::new();
-    let categorias: String = conn.query_row("SELECT value FROM config WHERE key = 'categorias'", [], |r| r.get(0)).unwrap_or_else(|_| "[]".to_string());
-    let contas: String = conn.query_row("SELECT value FROM config WHERE key = 'contas'", [], |r| r.get(0)).unwrap_or_else(|_| "[]".to_string());
-    let contas_investimento: String = conn.query_row("SELECT value FROM config WHERE key = 'contasInvestimento'", [], |r| r.get(0)).unwrap_or_else(|_| "[]".to_string());
-    let clientes: String = conn.query_row("SELECT value FROM config WHERE key = 'clientes'", [], |r| r.get(0)).unwrap_or_else(|_| "[]".to_string());
-    let status_lancamento: String = conn.query_row("SELECT value FROM config WHERE key = 'statusLancamento'", [], |r| r.get(0)).unwrap_or_else(|_| "[]".to_string());
-    let last_synced: Option<String> = conn.query_row("SELECT value FROM config WHERE key = 'lastSyncedAt'", [], |r| r.get(0)).ok();
-    map.insert("categorias".to_string(), serde_json::from_str(&categorias).unwrap_or(Value::Array(vec![])));
-    map.insert("contas".to_string(), serde_json::from_str(&contas).unwrap_or(Value::Array(vec![])));
-    map.insert("contasInvestimento".to_string(), serde_json::from_str(&contas_investimento).unwrap_or(Value::Array(vec![])));
-    map.insert("clientes".to_string(), serde_json::from_str(&clientes).unwrap_or(Value::Array(vec![])));
-    map.insert("statusLancamento".to_string(), serde_json::from_str(&status_lancamento).unwrap_or(Value::Array(vec![])));
-    map.insert("lastSyncedAt".to_string(), last_synced.map(Value::String).unwrap_or(Value::Null));
-    Ok(Value::Object(map))
-}
-
-pub fn set_config(conn: &Connection, key: &str, value: &str) -> Result<(), String> {
-    let updated_at = std::time::SystemTime::now()
-        .duration_since(std::time::UNIX_EPOCH)
+/// Resolve o banco que a migração 2 não soube classificar: `em_reais` (o frontend
+/// nunca converteu estes dados) multiplica todos os valores por 100, como edição
+/// local que vai no próximo push. Sem a marca, não faz nada. Devolve se converteu.
+pub fn resolver_valores_legados(conn: &Connection, em_reais: bool) -> Result<bool, AppError> {
+    let db_tx = conn.unchecked_transaction()?;
+    let marcado = db_tx.execute("DELETE FROM config WHERE key = ?1", [migrations::VALORES_AMBIGUOS_KEY])? > 0;
+    let converter = marcado && em_reais;
+    if converter {
+        let updated_at = now_iso();
+        let escrita = Escrita::Local { updated_at: &updated_at };
+        for tx in get_all_transacoes(&db_tx)? {
+            let value = Money::from_centavos(tx.value.centavos() * 100);
+            write_transacao(&db_tx, &Transacao { value, ..tx }, escrita)?;
+        }
+        for r in get_all_recorrentes(&db_tx)? {
+            let valor = Money::from_centavos(r.valor.centavos() * 100);
+            write_recorrencia(&db_tx, &Recorrencia { valor, ..r }, escrita)?;
+        }
+        eprintln!("[DB] valores legados em reais convertidos para centavos");
+    }
+    db_tx.commit()?;
+    Ok(converter)
+}
+
+pub fn config_value<T: DeserializeOwned + Default>(conn: &Connection, key: &str) -> T {
+    conn.query_row("SELECT value FROM config WHERE key = ?1", [key], |r| r.get::<_, String>(0))
         .ok()
-        .map(|d| d.as_secs().to_string())
-        .unwrap_or_else(|| "0".to_string());
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or_default()
+}
+
+pub fn get_config(conn: &Connection) -> Result<Config, AppError> {
+    Ok(Config {
+        categorias: config_value(conn, "categorias"),
+        contas: config_value(conn, "contas"),
+        contas_investimento: config_value(conn, "contasInvestimento"),
+        clientes: config_value(conn, "clientes"),
+        status_lancamento: config_value(conn, "statusLancamento"),
+        horizonte_recorrencias: config_value(conn, recorrencias::HORIZONTE_KEY),
+        last_synced_at: conn.query_row("SELECT value FROM config WHERE key = 'lastSyncedAt'", [], |r| r.get(0)).ok(),
+    })
+}
+
+pub fn put_config_value(conn: &Connection, key: &str, value: &str) -> Result<(), AppError> {
     conn.execute(
         "INSERT OR REPLACE INTO config (key, value, updated_at) VALUES (?1, ?2, ?3)",
-        params![key, value, updated_at],
-    )
-    .map_err(|e| e.to_string())?;
+        params![key, value, now_iso()],
+    )?;
     Ok(())
 }
 
-fn tx_to_api(t: &Value) -> Value {
-    t.clone()
+/// Grava uma chave de configuração (JSON em `value`). As listas conhecidas são
+/// validadas e normalizadas; as outras chaves vão como vieram e não sincronizam.
+pub fn set_config(conn: &Connection, key: &str, value: &str) -> Result<(), AppError> {
+    let parsed: Value = serde_json::from_str(value).map_err(|e| AppError::field("value", format!("JSON inválido: {}", e)))?;
+    let normalized = Config::normalize_entry(key, &parsed)?;
+    store_config_list(conn, key, &normalized)
 }
 
-pub fn set_auth_token(conn: &Connection, token: Option<&str>) -> Result<(), String> {
-    let value = token.unwrap_or("").to_string();
-    set_config(conn, AUTH_TOKEN_KEY, &value)
-}
-
-pub fn sync_pull(conn: &Connection, token_param: Option<String>) -> Result<(), String> {
-    let url = api_url();
-    if url.is_empty() {
-        return Ok(());
-    }
-    if let Some(ref t) = token_param {
-        if !t.is_empty() {
-            let _ = set_auth_token(conn, Some(t));
+// Grava uma lista de config já normalizada e cadastra as contas novas que ela citar.
+fn store_config_list(conn: &Connection, key: &str, normalized: &Value) -> Result<(), AppError> {
+    let value = normalized.to_string();
+    let atual: Option<String> = conn.query_row("SELECT value FROM config WHERE key = ?1", [key], |r| r.get(0)).ok();
+    if atual.as_deref() != Some(value.as_str()) {
+        put_config_value(conn, key, &value)?;
+        // Só as listas sincronizam; o resto (ex: horizonte) é deste aparelho
+        if CONFIG_LIST_KEYS.contains(&key) {
+            mark_changed(conn, CONFIG_ENTIDADE, key)?;
         }
     }
-    let token = token_param
-        .filter(|s| !s.is_empty())
-        .or_else(|| get_auth_token(conn));
-    let config = get_config(conn)?;
-    let since = config
-        .get("lastSyncedAt")
-        .and_then(|v| v.as_str())
-        .unwrap_or("");
-    let request_url = if since.is_empty() {
-        format!("{}/sync", url.trim_end_matches('/'))
-    } else {
-        format!("{}/sync?since={}", url.trim_end_matches('/'), urlencoding::encode(since))
+    let tipo = match key {
+        "contas" => TipoConta::Corrente,
+        "contasInvestimento" => TipoConta::Investimento,
+        _ => return Ok(()),
     };
-    let client = reqwest::blocking::Client::builder()
-        .timeout(std::time::Duration::from_secs(15))
-        .build()
-        .map_err(|e| e.to_string())?;
-    let mut req = client.get(&request_url);
-    if let Some(t) = &token {
-        req = req.header("Authorization", format!("Bearer {}", t));
-    }
-    let res = req.send().map_err(|e| e.to_string())?;
-    if !res.status().is_success() {
-        return Err(format!("sync pull failed: {}", res.status()));
-    }
-    let data: Value = res.json().map_err(|e| e.to_string())?;
-    if let Some(arr) = data.get("transacoes").and_then(|v| v.as_array()) {
-        for t in arr {
-            let _ = put_transacao(conn, tx_to_api(t));
-        }
+    for nome in normalized.as_array().into_iter().flatten().filter_map(|v| v.as_str()) {
+        contas::ensure_conta(conn, nome, tipo)?;
     }
-    if let Some(arr) = data.get("recorrentes").and_then(|v| v.as_array()) {
-        for r in arr {
-            let _ = put_recorrencia(conn, r.clone());
-        }
+    Ok(())
+}
+
+/// Payload do servidor (pull/restore) já desserializado e validado.
+#[derive(Default)]
+pub struct RemotePayload {
+    pub tombstones: Vec<Tombstone>,
+    pub transacoes: Vec<Transacao>,
+    pub recorrentes: Vec<Recorrencia>,
+    pub config: Vec<(&'static str, Value)>,
+    pub rejeitados: Vec<Rejeitado>,
+}
+
+/// Item do payload que não passou na validação, como veio, para não se perder.
+pub struct Rejeitado {
+    pub lista: &'static str,
+    pub id: String,
+    pub motivo: String,
+    pub dados: Value,
+}
+
+// Desserializa e valida cada item da lista `key`; os inválidos vão para `errors`
+// com o índice no campo, ex: transacoes[3].date, e inteiros para `rejeitados`.
+fn parse_items<T: DeserializeOwned>(
+    data: &Value,
+    key: &'static str,
+    validate: impl Fn(&T) -> Result<(), ValidationErrors>,
+    errors: &mut ValidationErrors,
+    rejeitados: &mut Vec<Rejeitado>,
+) -> Vec<T> {
+    let mut out = vec![];
+    for (i, item) in data.get(key).and_then(|v| v.as_array()).into_iter().flatten().enumerate() {
+        let prefix = format!("{}[{}]", key, i);
+        let erro = match serde_json::from_value::<T>(item.clone()) {
+            Ok(v) => match validate(&v) {
+                Ok(()) => {
+                    out.push(v);
+                    continue;
+                }
+                Err(e) => e.prefixed(&prefix),
+            },
+            Err(e) => {
+                let mut erro = ValidationErrors::default();
+                erro.add(prefix.clone(), e.to_string());
+                erro
+            }
+        };
+        rejeitados.push(Rejeitado {
+            lista: key,
+            id: item.get("id").and_then(Value::as_str).map_or(prefix, str::to_string),
+            motivo: erro.to_string(),
+            dados: item.clone(),
+        });
+        errors.0.extend(erro.0);
     }
+    out
+}
+
+/// Lê o payload inteiro antes de gravar qualquer coisa: devolve os itens válidos e
+/// o relatório dos rejeitados.
+pub fn parse_remote(data: &Value) -> (RemotePayload, ValidationErrors) {
+    let mut errors = ValidationErrors::default();
+    let mut rejeitados = vec![];
+    let mut payload = RemotePayload {
+        tombstones: parse_items(data, "tombstones", |_: &Tombstone| Ok(()), &mut errors, &mut rejeitados),
+        transacoes: parse_items(data, "transacoes", Transacao::validate, &mut errors, &mut rejeitados),
+        recorrentes: parse_items(data, "recorrentes", Recorrencia::validate, &mut errors, &mut rejeitados),
+        ..Default::default()
+    };
     if let Some(cfg) = data.get("config") {
-        if let Some(cats) = cfg.get("categorias") {
-            let _ = set_config(conn, "categorias", &cats.to_string());
+        for key in CONFIG_LIST_KEYS {
+            if let Some(v) = cfg.get(key) {
+                match Config::normalize_entry(key, v) {
+                    Ok(n) => payload.config.push((key, n)),
+                    Err(e) => {
+                        let erro = e.prefixed("config");
+                        rejeitados.push(Rejeitado { lista: "config", id: key.to_string(), motivo: erro.to_string(), dados: v.clone() });
+                        errors.0.extend(erro.0);
+                    }
+                }
+            }
         }
-        if let Some(contas) = cfg.get("contas") {
-            let _ = set_config(conn, "contas", &contas.to_string());
+    }
+    payload.rejeitados = rejeitados;
+    (payload, errors)
+}
+
+// Erro de validação ao aplicar um item leva o caminho do item no payload.
+fn no_item(path: String) -> impl Fn(AppError) -> AppError {
+    move |e| match e {
+        AppError::Validation(v) => AppError::Validation(v.prefixed(&path)),
+        e => e,
+    }
+}
+
+// Aplica um payload já validado. Exclusões definitivas vêm primeiro, registros com
+// lápide local não são recriados e cada registro passa pelo merge de
+// `conflitos::aplicar_remoto`. Deve rodar dentro da transação de quem chama.
+fn apply_remote(conn: &Connection, payload: &RemotePayload) -> Result<(), AppError> {
+    tombstones::aplicar_remotas(conn, &payload.tombstones).map_err(no_item("tombstones".to_string()))?;
+    for (i, tx) in payload.transacoes.iter().enumerate() {
+        if !tombstones::existe(conn, Entidade::Transacao, &tx.id)? {
+            conflitos::aplicar_remoto(conn, Entidade::Transacao, &serde_json::to_value(tx).unwrap_or_default())
+                .map_err(no_item(format!("transacoes[{}]", i)))?;
         }
-        if let Some(ci) = cfg.get("contasInvestimento") {
-            let _ = set_config(conn, "contasInvestimento", &ci.to_string());
+    }
+    for (i, r) in payload.recorrentes.iter().enumerate() {
+        if !tombstones::existe(conn, Entidade::Recorrencia, &r.id)? {
+            conflitos::aplicar_remoto(conn, Entidade::Recorrencia, &serde_json::to_value(r).unwrap_or_default())
+                .map_err(no_item(format!("recorrentes[{}]", i)))?;
         }
-        if let Some(cli) = cfg.get("clientes") {
-            let _ = set_config(conn, "clientes", &cli.to_string());
+    }
+    // Listas de config não têm versão: uma lista alterada aqui e ainda não enviada
+    // vence, e a do servidor é ignorada até o próximo push.
+    for (key, v) in &payload.config {
+        if !is_pending(conn, CONFIG_ENTIDADE, key)? {
+            store_config_list(conn, key, v).map_err(no_item(format!("config.{}", key)))?;
         }
-        if let Some(sl) = cfg.get("statusLancamento") {
-            let _ = set_config(conn, "statusLancamento", &sl.to_string());
+    }
+    Ok(())
+}
+
+// Guarda o cursor da resposta do GET /sync, na transação que aplica os dados dela.
+// Servidor antigo não manda cursor; aí o próximo pull vem completo.
+fn store_cursor(conn: &Connection, data: &Value) -> Result<(), AppError> {
+    match data.get("cursor").and_then(Value::as_u64) {
+        Some(c) => put_config_value(conn, CURSOR_SYNC_KEY, &c.to_string()),
+        None => {
+            conn.execute("DELETE FROM config WHERE key = ?1", [CURSOR_SYNC_KEY])?;
+            Ok(())
         }
     }
-    let now = std::time::SystemTime::now()
-        .duration_since(std::time::UNIX_EPOCH)
-        .ok()
-        .map(|d| d.as_secs().to_string())
-        .unwrap_or_else(|| "0".to_string());
-    let _ = set_config(conn, "lastSyncedAt", &now);
+}
+
+/// Resultado de um pull: registros aplicados (lápides incluídas) e itens recusados.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ResultadoPull {
+    pub recebidos: usize,
+    pub rejeitados: usize,
+}
+
+// Guarda os itens recusados em `registros_invalidos`, como a migração 3 faz com as
+// linhas locais: o cursor avança, então o servidor não os manda de novo.
+fn guardar_rejeitados(conn: &Connection, rejeitados: &[Rejeitado]) -> rusqlite::Result<()> {
+    for r in rejeitados {
+        conn.execute(
+            "INSERT OR REPLACE INTO registros_invalidos (tabela, id, motivo, dados) VALUES (?1, ?2, ?3, ?4)",
+            params![r.lista, r.id, r.motivo, r.dados.to_string()],
+        )?;
+    }
     Ok(())
 }
 
-pub fn sync_push(conn: &Connection) -> Result<(), String> {
-    let url = api_url();
-    if url.is_empty() {
-        return Ok(());
-    }
-    let token = get_auth_token(conn);
-    let transacoes = get_all_transacoes(conn)?;
-    let recorrentes = get_all_recorrentes(conn)?;
-    let config = get_config(conn)?;
-    let categorias = config.get("categorias").cloned().unwrap_or(Value::Array(vec![]));
-    let contas = config.get("contas").cloned().unwrap_or(Value::Array(vec![]));
-    let contas_investimento = config.get("contasInvestimento").cloned().unwrap_or(Value::Array(vec![]));
-    let clientes = config.get("clientes").cloned().unwrap_or(Value::Array(vec![]));
-    let status_lancamento = config.get("statusLancamento").cloned().unwrap_or(Value::Array(vec![]));
+/// Aplica a resposta do GET /sync.
+pub fn apply_pull(conn: &Connection, data: &Value) -> Result<ResultadoPull, AppError> {
+    // Itens inválidos ficam guardados à parte; os demais entram juntos, numa
+    // transação só.
+    let (payload, rejeitados) = parse_remote(data);
+    if !rejeitados.is_empty() {
+        eprintln!("[Sync] itens rejeitados no pull: {}", rejeitados);
+    }
+    let db_tx = conn.unchecked_transaction()?;
+    apply_remote(&db_tx, &payload)?;
+    guardar_rejeitados(&db_tx, &payload.rejeitados)?;
+    store_cursor(&db_tx, data)?;
+    db_tx.commit()?;
+    let _ = put_config_value(conn, "lastSyncedAt", &now_iso());
+    Ok(ResultadoPull {
+        recebidos: payload.tombstones.len() + payload.transacoes.len() + payload.recorrentes.len() + payload.config.len(),
+        rejeitados: payload.rejeitados.len(),
+    })
+}
+
+/// Corpo de um push e o que ele cobre, para confirmar depois da resposta.
+pub struct PushBatch {
+    pub body: Value,
+    pending: PendingChanges,
+    lapides: Vec<Tombstone>,
+}
+
+/// Monta o push com o que está no outbox, ou `None` se não há nada a enviar.
+pub fn push_batch(conn: &Connection) -> Result<Option<PushBatch>, AppError> {
+    let pending = pending_changes(conn)?;
+    let lapides = tombstones::pendentes(conn)?;
+    if pending.is_empty() && lapides.is_empty() {
+        return Ok(None);
+    }
+    // `delta`: o servidor faz merge das recorrentes em vez de substituir a lista
     let body = serde_json::json!({
-        "transacoes": transacoes,
-        "recorrentes": recorrentes,
-        "config": {
-            "categorias": categorias,
-            "contas": contas,
-            "contasInvestimento": contas_investimento,
-            "clientes": clientes,
-            "statusLancamento": status_lancamento
-        }
+        "delta": true,
+        "deviceId": tombstones::device_id(conn)?,
+        "tombstones": lapides,
+        "transacoes": pending.transacoes,
+        "recorrentes": pending.recorrentes,
+        "config": pending.config,
     });
-    let client = reqwest::blocking::Client::builder()
-        .timeout(std::time::Duration::from_secs(15))
-        .build()
-        .map_err(|e| e.to_string())?;
-    let mut req = client
-        .post(format!("{}/sync", url.trim_end_matches('/')))
-        .json(&body);
-    if let Some(t) = &token {
-        req = req.header("Authorization", format!("Bearer {}", t));
-    }
-    let res = req.send().map_err(|e| e.to_string())?;
-    if !res.status().is_success() {
-        return Err(format!("sync push failed: {}", res.status()));
-    }
-    let now = std::time::SystemTime::now()
-        .duration_since(std::time::UNIX_EPOCH)
-        .ok()
-        .map(|d| d.as_secs().to_string())
-        .unwrap_or_else(|| "0".to_string());
-    let _ = set_config(conn, "lastSyncedAt", &now);
+    Ok(Some(PushBatch { body, pending, lapides }))
+}
+
+/// Confirma um push aceito pelo servidor.
+pub fn confirm_push(conn: &Connection, batch: &PushBatch) -> Result<(), AppError> {
+    // Só o que foi enviado sai do outbox; o que mudou durante o push tem seq maior e
+    // registros em conflito ficam retidos até o usuário resolver. A confirmação é
+    // atômica: se o app cair no meio, o próximo push reenvia tudo (o merge no
+    // servidor é idempotente).
+    let db_tx = conn.unchecked_transaction()?;
+    db_tx.execute(
+        "DELETE FROM change_log WHERE seq <= ?1
+         AND NOT EXISTS (SELECT 1 FROM conflitos c WHERE c.entidade = change_log.entidade AND c.id = change_log.id)",
+        [batch.pending.max_seq],
+    )?;
+    for tx in &batch.pending.transacoes {
+        conflitos::registrar_enviado(&db_tx, Entidade::Transacao, &serde_json::to_value(tx).unwrap_or_default())?;
+    }
+    for r in &batch.pending.recorrentes {
+        conflitos::registrar_enviado(&db_tx, Entidade::Recorrencia, &serde_json::to_value(r).unwrap_or_default())?;
+    }
+    tombstones::marcar_enviadas(&db_tx, &batch.lapides)?;
+    // O cursor do pull não avança aqui: o que outros aparelhos enviaram desde o último
+    // pull ainda não chegou
+    put_config_value(&db_tx, "lastSyncedAt", &now_iso())?;
+    db_tx.commit()?;
     Ok(())
 }
 
-pub fn restore_from_cloud(conn: &Connection) -> Result<(), String> {
-    let url = api_url();
-    if url.is_empty() {
-        return Err("API URL não configurada. Defina TAURI_APP_CLOUD_API_URL.".to_string());
-    }
-    let token = get_auth_token(conn);
-    if token.is_none() {
-        return Err("Token de autenticação não encontrado. Faça login primeiro.".to_string());
-    }
-    let client = reqwest::blocking::Client::builder()
-        .timeout(std::time::Duration::from_secs(30))
-        .build()
-        .map_err(|e| e.to_string())?;
-    let mut req = client.get(format!("{}/sync", url.trim_end_matches('/')));
-    if let Some(t) = &token {
-        req = req.header("Authorization", format!("Bearer {}", t));
-    }
-    let res = req.send().map_err(|e| e.to_string())?;
-    if !res.status().is_success() {
-        return Err(format!("restore failed: {}", res.status()));
-    }
-    let data: Value = res.json().map_err(|e| e.to_string())?;
-    conn.execute("DELETE FROM transacoes", []).map_err(|e| e.to_string())?;
-    conn.execute("DELETE FROM recorrentes", []).map_err(|e| e.to_string())?;
-    if let Some(arr) = data.get("transacoes").and_then(|v| v.as_array()) {
-        for t in arr {
-            let _ = put_transacao(conn, tx_to_api(t));
-        }
+/// Como o restore trata os dados locais: `Substituir` apaga tudo e deixa só o que
+/// veio da nuvem; `Mesclar` aplica a nuvem por cima, como um pull completo.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ModoRestauracao {
+    #[default]
+    Substituir,
+    Mesclar,
+}
+
+/// Aplica o snapshot completo do servidor segundo o modo.
+pub fn apply_restore(conn: &Connection, data: &Value, modo: ModoRestauracao) -> Result<(), AppError> {
+    // Payload com qualquer item inválido não apaga nada: o erro traz cada
+    // rejeitado, ex: transacoes[3].date
+    let (payload, rejeitados) = parse_remote(data);
+    rejeitados.into_result()?;
+    // Tudo numa transação; se um registro falhar, o banco local fica como estava.
+    let db_tx = conn.unchecked_transaction()?;
+    if modo == ModoRestauracao::Substituir {
+        // O servidor passa a ser a verdade: edições locais pendentes e bases de
+        // merge dos registros apagados vão junto, e o que chega é escrita replicada.
+        db_tx.execute_batch(
+            "DELETE FROM transacoes;
+             DELETE FROM recorrentes;
+             DELETE FROM change_log WHERE entidade IN ('transacao', 'recorrencia');
+             DELETE FROM sync_base;
+             DELETE FROM conflitos;",
+        )?;
     }
-    if let Some(arr) = data.get("recorrentes").and_then(|v| v.as_array()) {
-        for r in arr {
-            let _ = put_recorrencia(conn, r.clone());
-        }
+    apply_remote(&db_tx, &payload)?;
+    store_cursor(&db_tx, data)?;
+    put_config_value(&db_tx, "lastSyncedAt", &now_iso())?;
+    db_tx.commit()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn banco() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        migrate(&conn).unwrap();
+        conn
     }
-    if let Some(cfg) = data.get("config") {
-        if let Some(cats) = cfg.get("categorias") {
-            let _ = set_config(conn, "categorias", &cats.to_string());
-        }
-        if let Some(contas) = cfg.get("contas") {
-            let _ = set_config(conn, "contas", &contas.to_string());
-        }
-        if let Some(ci) = cfg.get("contasInvestimento") {
-            let _ = set_config(conn, "contasInvestimento", &ci.to_string());
-        }
-        if let Some(cli) = cfg.get("clientes") {
-            let _ = set_config(conn, "clientes", &cli.to_string());
-        }
-        if let Some(sl) = cfg.get("statusLancamento") {
-            let _ = set_config(conn, "statusLancamento", &sl.to_string());
+
+    #[test]
+    fn pull_guarda_itens_invalidos_e_aplica_o_resto() {
+        let conn = banco();
+        let data = json!({
+            "cursor": 7,
+            "transacoes": [
+                { "id": "t1", "date": "2026-03-10", "value": 1990, "type": "saida" },
+                { "id": "t2", "date": "10/03/2026", "value": 500, "type": "saida" },
+            ],
+        });
+        let resultado = apply_pull(&conn, &data).unwrap();
+        assert_eq!(resultado, ResultadoPull { recebidos: 1, rejeitados: 1 });
+        assert!(get_transacao(&conn, "t1").unwrap().is_some());
+        let (tabela, dados): (String, String) = conn
+            .query_row("SELECT tabela, dados FROM registros_invalidos WHERE id = 't2'", [], |r| Ok((r.get(0)?, r.get(1)?)))
+            .unwrap();
+        assert_eq!(tabela, "transacoes");
+        assert_eq!(serde_json::from_str::<Value>(&dados).unwrap(), data["transacoes"][1]);
+        assert_eq!(config_value::<Option<u64>>(&conn, CURSOR_SYNC_KEY), Some(7));
+    }
+
+    fn transacao(id: &str, centavos: i64) -> Transacao {
+        serde_json::from_value(json!({ "id": id, "date": "2026-03-10", "value": centavos, "type": "saida" })).unwrap()
+    }
+
+    #[test]
+    fn confirmar_push_mantem_pendente_o_editado_durante_o_envio() {
+        let conn = banco();
+        put_transacoes(&conn, &[transacao("t1", 100), transacao("t2", 200)]).unwrap();
+        let batch = push_batch(&conn).unwrap().unwrap();
+        assert_eq!(batch.body["transacoes"].as_array().unwrap().len(), 2);
+        // Editado enquanto a requisição estava no ar
+        put_transacao(&conn, &transacao("t1", 150)).unwrap();
+        confirm_push(&conn, &batch).unwrap();
+        assert!(is_pending(&conn, "transacao", "t1").unwrap());
+        assert!(!is_pending(&conn, "transacao", "t2").unwrap());
+        let proximo = push_batch(&conn).unwrap().unwrap();
+        let enviados = proximo.body["transacoes"].as_array().unwrap();
+        assert_eq!(enviados.len(), 1);
+        assert_eq!((&enviados[0]["id"], &enviados[0]["value"]), (&json!("t1"), &json!(150)));
+    }
+
+    #[test]
+    fn restore_com_item_invalido_nao_mexe_no_banco() {
+        let conn = banco();
+        put_transacao(&conn, &transacao("t1", 100)).unwrap();
+        let data = json!({
+            "transacoes": [
+                { "id": "t9", "date": "2026-03-10", "value": 900, "type": "saida" },
+                { "id": "t10", "date": "2026-03-10", "value": 100, "type": "transferencia" },
+            ],
+        });
+        let erro = apply_restore(&conn, &data, ModoRestauracao::Substituir).unwrap_err();
+        match erro {
+            AppError::Validation(v) => assert!(v.0.iter().all(|e| e.field.starts_with("transacoes[1]"))),
+            e => panic!("esperava erro de validação, veio {:?}", e),
         }
+        assert!(get_transacao(&conn, "t1").unwrap().is_some());
+        assert!(get_transacao(&conn, "t9").unwrap().is_none());
+        assert!(is_pending(&conn, "transacao", "t1").unwrap());
     }
-    let now = std::time::SystemTime::now()
-        .duration_since(std::time::UNIX_EPOCH)
-        .ok()
-        .map(|d| d.as_secs().to_string())
-        .unwrap_or_else(|| "0".to_string());
-    let _ = set_config(conn, "lastSyncedAt", &now);
-    Ok(())
 }
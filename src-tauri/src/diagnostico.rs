@@ -0,0 +1,240 @@
+use std::path::{Path, PathBuf};
+
+use rusqlite::{Connection, ErrorCode, OpenFlags};
+use serde::Serialize;
+
+use crate::credenciais;
+use crate::criptografia;
+use crate::error::AppError;
+use crate::migrations;
+use crate::pool::{self, ChaveBanco, Pool};
+
+pub const ARQUIVO_BANCO: &str = "vertexads.db";
+// Na pasta do app: caminho da pasta de dados escolhida pelo usuário
+const ARQUIVO_PASTA: &str = "pasta-dados";
+// Linhas do integrity_check guardadas no status; o resto só repete o problema
+const LINHAS_INTEGRIDADE: u32 = 20;
+
+/// Por que o banco não abriu (ou abriu com defeito).
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Problema {
+    /// Cifrado com senha: falta `desbloquear_banco`.
+    SenhaNecessaria,
+    /// A chave do chaveiro não abre o banco.
+    ChaveIncorreta,
+    /// Sem permissão de escrita no arquivo ou na pasta.
+    SomenteLeitura,
+    /// Outro processo (outra instância do app, antivírus, backup) prende o arquivo.
+    EmUso,
+    /// Arquivo ilegível ou com falhas no `integrity_check`.
+    Corrompido,
+    /// Schema de uma versão mais nova do app.
+    VersaoNova,
+    Indisponivel,
+}
+
+/// Payload de `inicio://status` e resposta de `get_status_inicio`. Com `aberto`
+/// e um problema (ex: corrompido), os comandos funcionam, para dar tempo de
+/// salvar o que der antes de recuperar.
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StatusInicio {
+    pub ok: bool,
+    pub aberto: bool,
+    pub caminho: String,
+    pub problema: Option<Problema>,
+    pub mensagem: Option<String>,
+    /// Linhas do `integrity_check`, quando ele acusa algo.
+    pub integridade: Vec<String>,
+    /// Onde ficou o arquivo anterior, depois de uma recuperação.
+    pub guardado_em: Option<String>,
+}
+
+impl StatusInicio {
+    fn novo(path: &Path, aberto: bool, problema: Option<Problema>, mensagem: Option<String>) -> Self {
+        StatusInicio {
+            ok: aberto && problema.is_none(),
+            aberto,
+            caminho: path.display().to_string(),
+            problema,
+            mensagem,
+            integridade: Vec::new(),
+            guardado_em: None,
+        }
+    }
+}
+
+// Problemas do `integrity_check`; vazio quando o banco está íntegro.
+fn integridade(conn: &Connection) -> rusqlite::Result<Vec<String>> {
+    let mut stmt = conn.prepare(&format!("PRAGMA integrity_check({})", LINHAS_INTEGRIDADE))?;
+    let linhas = stmt.query_map([], |r| r.get::<_, String>(0))?.collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(if linhas == ["ok"] { Vec::new() } else { linhas })
+}
+
+/// Abre o banco do startup, com a chave do chaveiro se ele for cifrado. Na falha,
+/// devolve o diagnóstico do porquê.
+pub fn abrir(path: &Path) -> Result<Pool, StatusInicio> {
+    criptografia::abrir(path).map_err(|(chave, e)| falha(path, chave.as_ref(), &e))
+}
+
+/// Diagnóstico do banco aberto: `integrity_check` numa conexão de leitura.
+pub fn verificar(path: &Path, p: &Pool) -> StatusInicio {
+    let c = match p.leitura() {
+        Ok(c) => c,
+        Err(e) => return StatusInicio::novo(path, true, Some(Problema::Indisponivel), Some(e.to_string())),
+    };
+    match integridade(&c) {
+        Ok(linhas) if linhas.is_empty() => StatusInicio::novo(path, true, None, None),
+        Ok(linhas) => {
+            let mut status = StatusInicio::novo(
+                path,
+                true,
+                Some(Problema::Corrompido),
+                Some("o integrity_check encontrou problemas no banco".to_string()),
+            );
+            status.integridade = linhas;
+            status
+        }
+        // Página tão estragada que o próprio integrity_check não termina
+        Err(e) => {
+            let problema = match e.sqlite_error_code() {
+                Some(ErrorCode::DatabaseCorrupt | ErrorCode::NotADatabase) => Problema::Corrompido,
+                _ => Problema::Indisponivel,
+            };
+            StatusInicio::novo(path, true, Some(problema), Some(e.to_string()))
+        }
+    }
+}
+
+/// Diagnóstico de um banco que não abriu.
+pub fn falha(path: &Path, chave: Option<&ChaveBanco>, erro: &AppError) -> StatusInicio {
+    let problema = match erro {
+        AppError::DbLocked(_) => Problema::SenhaNecessaria,
+        AppError::WrongKey(_) => Problema::ChaveIncorreta,
+        _ => sondar(path, chave).unwrap_or(Problema::Indisponivel),
+    };
+    StatusInicio::novo(path, false, Some(problema), Some(erro.to_string()))
+}
+
+/// A pasta aceita arquivos novos (o SQLite cria o -wal e o -shm ao lado do banco).
+pub fn pasta_gravavel(dir: &Path) -> bool {
+    let teste = dir.join(".vertexads-teste");
+    let ok = std::fs::write(&teste, b"").is_ok();
+    let _ = std::fs::remove_file(&teste);
+    ok
+}
+
+// Reabre o arquivo fora do pool para achar o motivo da falha pelo código do
+// SQLite, que o AppError já não traz.
+fn sondar(path: &Path, chave: Option<&ChaveBanco>) -> Option<Problema> {
+    let somente_leitura = std::fs::metadata(path).is_ok_and(|m| m.permissions().readonly());
+    if somente_leitura || !pasta_gravavel(path.parent().unwrap_or_else(|| Path::new("."))) {
+        return Some(Problema::SomenteLeitura);
+    }
+    let sonda = (|| -> rusqlite::Result<(i64, Vec<String>)> {
+        let conn = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_WRITE)?;
+        if let Some(c) = chave {
+            conn.pragma_update(None, "key", c.pragma())?;
+        }
+        // Sem busy_timeout: arquivo preso por outro processo falha na hora
+        conn.execute_batch("BEGIN IMMEDIATE; ROLLBACK;")?;
+        let versao = conn.query_row("PRAGMA user_version", [], |r| r.get(0))?;
+        Ok((versao, integridade(&conn)?))
+    })();
+    match sonda {
+        Ok((versao, _)) if versao > migrations::latest_version() => Some(Problema::VersaoNova),
+        Ok((_, linhas)) if !linhas.is_empty() => Some(Problema::Corrompido),
+        Ok(_) => None,
+        Err(rusqlite::Error::SqliteFailure(f, _)) => match f.code {
+            ErrorCode::DatabaseBusy | ErrorCode::DatabaseLocked => Some(Problema::EmUso),
+            ErrorCode::ReadOnly | ErrorCode::CannotOpen | ErrorCode::PermissionDenied => Some(Problema::SomenteLeitura),
+            ErrorCode::NotADatabase | ErrorCode::DatabaseCorrupt => Some(Problema::Corrompido),
+            _ => None,
+        },
+        Err(_) => None,
+    }
+}
+
+/// Acha a chave que abre o backup `origem` e confere a integridade e a versão
+/// dele antes de usá-lo. Sem `senha`, tenta a chave do banco atual, a do
+/// chaveiro e sem cifra.
+pub fn conferir_backup(origem: &Path, senha: Option<String>, atual: Option<&Pool>) -> Result<Option<ChaveBanco>, AppError> {
+    if !origem.is_file() {
+        return Err(AppError::field("caminho", "arquivo não encontrado"));
+    }
+    let candidatas = match senha {
+        Some(s) => vec![Some(ChaveBanco::Senha(s))],
+        None => {
+            let mut c = Vec::new();
+            if let Some(k) = atual.map(Pool::chave).transpose()?.flatten() {
+                c.push(Some(k));
+            }
+            if let Some(hex) = credenciais::chave_banco() {
+                c.push(Some(ChaveBanco::Bruta(hex)));
+            }
+            c.push(None);
+            c
+        }
+    };
+    // Sem chave para tentar, o cabeçalho já diz se falta a senha
+    let mut cifrado = criptografia::cifrado(origem);
+    let mut ultimo = None;
+    for chave in candidatas {
+        let conn = match pool::abrir_leitura(origem, chave.as_ref()) {
+            Ok(c) => c,
+            Err(e) => {
+                cifrado |= matches!(e, AppError::WrongKey(_));
+                ultimo = Some(e);
+                continue;
+            }
+        };
+        if migrations::current_version(&conn)? > migrations::latest_version() {
+            return Err(AppError::field("caminho", "backup de uma versão mais nova do app"));
+        }
+        let linhas = integridade(&conn)?;
+        if !linhas.is_empty() {
+            return Err(AppError::field("caminho", format!("backup corrompido: {}", linhas.join("; "))));
+        }
+        return Ok(chave);
+    }
+    Err(match ultimo {
+        Some(_) if cifrado => AppError::WrongKey("backup cifrado com outra chave: informe a senha dele".to_string()),
+        Some(e) => e,
+        None => AppError::Internal("nenhuma chave para tentar".to_string()),
+    })
+}
+
+/// Pasta de dados escolhida pelo usuário, gravada na pasta do app.
+pub fn pasta_escolhida(pasta_app: &Path) -> Option<PathBuf> {
+    let texto = std::fs::read_to_string(pasta_app.join(ARQUIVO_PASTA)).ok()?;
+    Some(PathBuf::from(texto.trim())).filter(|p| !p.as_os_str().is_empty())
+}
+
+/// Passa a guardar o banco em `pasta` (`None`: volta à pasta do app) a partir da
+/// próxima abertura. Com `copiar`, leva uma cópia do banco atual; sem, usa o que
+/// houver lá ou começa um vazio. Devolve o caminho do banco novo.
+pub fn escolher_pasta(pasta_app: &Path, pasta: Option<&Path>, atual: Option<&Pool>, copiar: bool) -> Result<PathBuf, AppError> {
+    let destino_pasta = pasta.unwrap_or(pasta_app);
+    std::fs::create_dir_all(destino_pasta).map_err(|e| AppError::field("pasta", e.to_string()))?;
+    if !pasta_gravavel(destino_pasta) {
+        return Err(AppError::field("pasta", "sem permissão de escrita"));
+    }
+    let destino = destino_pasta.join(ARQUIVO_BANCO);
+    if copiar {
+        if destino.exists() {
+            return Err(AppError::Conflict(format!("já existe um banco em {}", destino.display())));
+        }
+        atual.ok_or_else(|| AppError::field("copiar", "o banco atual não está aberto"))?.copiar_para(&destino)?;
+    }
+    let preferencia = pasta_app.join(ARQUIVO_PASTA);
+    let gravado = match pasta {
+        Some(p) => std::fs::write(&preferencia, p.to_string_lossy().as_bytes()),
+        None => std::fs::remove_file(&preferencia).or_else(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => Ok(()),
+            _ => Err(e),
+        }),
+    };
+    gravado.map_err(|e| AppError::Internal(format!("preferência da pasta de dados: {}", e)))?;
+    Ok(destino)
+}
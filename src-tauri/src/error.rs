@@ -0,0 +1,182 @@
+use serde::{Serialize, Serializer};
+
+use crate::migrations::MigrationError;
+use crate::models::ValidationErrors;
+
+/// Erro devolvido por todos os comandos. Serializado como
+/// `{ kind, code, message, fields?, status? }` para o frontend decidir a reação
+/// pelo `code` (ex: UNAUTHORIZED leva ao login).
+#[derive(Debug)]
+pub enum AppError {
+    DbUnavailable(String),
+    /// Banco cifrado com senha, ainda não informada nesta sessão.
+    DbLocked(String),
+    /// Chave ou senha do banco cifrado, ou PIN do bloqueio, não confere.
+    WrongKey(String),
+    /// App bloqueado por PIN ou senha; só os comandos de bloqueio respondem.
+    Locked(String),
+    /// Tentativas erradas demais; a mensagem diz quanto esperar.
+    RateLimited(String),
+    Database(String),
+    Validation(ValidationErrors),
+    Network(String),
+    Unauthorized(String),
+    Conflict(String),
+    NotFound(String),
+    Server { status: u16, message: String },
+    Internal(String),
+}
+
+impl AppError {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            AppError::DbUnavailable(_) => "DbUnavailable",
+            AppError::DbLocked(_) => "DbLocked",
+            AppError::WrongKey(_) => "WrongKey",
+            AppError::Locked(_) => "Locked",
+            AppError::RateLimited(_) => "RateLimited",
+            AppError::Database(_) => "Database",
+            AppError::Validation(_) => "Validation",
+            AppError::Network(_) => "Network",
+            AppError::Unauthorized(_) => "Unauthorized",
+            AppError::Conflict(_) => "Conflict",
+            AppError::NotFound(_) => "NotFound",
+            AppError::Server { .. } => "Server",
+            AppError::Internal(_) => "Internal",
+        }
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            AppError::DbUnavailable(_) => "DB_UNAVAILABLE",
+            AppError::DbLocked(_) => "DB_LOCKED",
+            AppError::WrongKey(_) => "WRONG_KEY",
+            AppError::Locked(_) => "APP_LOCKED",
+            AppError::RateLimited(_) => "RATE_LIMITED",
+            AppError::Database(_) => "DATABASE",
+            AppError::Validation(_) => "VALIDATION",
+            AppError::Network(_) => "NETWORK",
+            AppError::Unauthorized(_) => "UNAUTHORIZED",
+            AppError::Conflict(_) => "CONFLICT",
+            AppError::NotFound(_) => "NOT_FOUND",
+            AppError::Server { .. } => "SERVER",
+            AppError::Internal(_) => "INTERNAL",
+        }
+    }
+
+    /// Erro de validação de um único campo.
+    pub fn field(field: &str, message: impl Into<String>) -> Self {
+        let mut errors = ValidationErrors::default();
+        errors.add(field, message);
+        AppError::Validation(errors)
+    }
+
+    /// Converte uma resposta HTTP sem sucesso do servidor de sync.
+    pub fn from_status(status: reqwest::StatusCode, context: &str) -> Self {
+        let message = format!("{}: {}", context, status);
+        match status.as_u16() {
+            401 | 403 => AppError::Unauthorized(message),
+            404 => AppError::NotFound(message),
+            409 => AppError::Conflict(message),
+            code => AppError::Server { status: code, message },
+        }
+    }
+}
+
+impl std::fmt::Display for AppError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AppError::Validation(errors) => write!(f, "dados inválidos: {}", errors),
+            AppError::Server { message, .. } => write!(f, "{}", message),
+            AppError::DbUnavailable(m)
+            | AppError::DbLocked(m)
+            | AppError::WrongKey(m)
+            | AppError::Locked(m)
+            | AppError::RateLimited(m)
+            | AppError::Database(m)
+            | AppError::Network(m)
+            | AppError::Unauthorized(m)
+            | AppError::Conflict(m)
+            | AppError::NotFound(m)
+            | AppError::Internal(m) => write!(f, "{}", m),
+        }
+    }
+}
+
+impl std::error::Error for AppError {}
+
+impl Serialize for AppError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        #[derive(Serialize)]
+        struct Wire<'a> {
+            kind: &'static str,
+            code: &'static str,
+            message: String,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            fields: Option<&'a ValidationErrors>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            status: Option<u16>,
+        }
+        Wire {
+            kind: self.kind(),
+            code: self.code(),
+            message: self.to_string(),
+            fields: match self {
+                AppError::Validation(errors) => Some(errors),
+                _ => None,
+            },
+            status: match self {
+                AppError::Server { status, .. } => Some(*status),
+                AppError::Unauthorized(_) => Some(401),
+                _ => None,
+            },
+        }
+        .serialize(serializer)
+    }
+}
+
+impl From<rusqlite::Error> for AppError {
+    fn from(e: rusqlite::Error) -> Self {
+        use rusqlite::ErrorCode;
+        match &e {
+            rusqlite::Error::QueryReturnedNoRows => AppError::NotFound(e.to_string()),
+            rusqlite::Error::SqliteFailure(f, _) => match f.code {
+                ErrorCode::ConstraintViolation => AppError::Conflict(e.to_string()),
+                ErrorCode::DatabaseBusy
+                | ErrorCode::DatabaseLocked
+                | ErrorCode::CannotOpen
+                | ErrorCode::NotADatabase
+                | ErrorCode::ReadOnly => AppError::DbUnavailable(e.to_string()),
+                _ => AppError::Database(e.to_string()),
+            },
+            _ => AppError::Database(e.to_string()),
+        }
+    }
+}
+
+impl From<reqwest::Error> for AppError {
+    fn from(e: reqwest::Error) -> Self {
+        match e.status() {
+            Some(status) => AppError::from_status(status, "sync"),
+            None => AppError::Network(e.to_string()),
+        }
+    }
+}
+
+impl From<ValidationErrors> for AppError {
+    fn from(e: ValidationErrors) -> Self {
+        AppError::Validation(e)
+    }
+}
+
+impl From<MigrationError> for AppError {
+    fn from(e: MigrationError) -> Self {
+        AppError::DbUnavailable(e.to_string())
+    }
+}
+
+impl<T> From<std::sync::PoisonError<T>> for AppError {
+    fn from(e: std::sync::PoisonError<T>) -> Self {
+        AppError::Internal(e.to_string())
+    }
+}
@@ -0,0 +1,365 @@
+use std::collections::HashSet;
+use std::path::Path;
+
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use sha2::{Digest, Sha256};
+
+use crate::db::{self, Escrita, ModoRestauracao};
+use crate::error::AppError;
+use crate::migrations;
+use crate::models::{Config, Entidade, Recorrencia, Transacao, ValidationErrors, CONFIG_LIST_KEYS};
+use crate::recorrencias;
+use crate::tombstones;
+
+const FORMATO: &str = "vertexads-financeiro";
+// Versão do documento; mude só se o formato do JSON mudar, não a cada migração.
+const VERSAO: u32 = 1;
+const PREFIXO_CHECKSUM: &str = "sha256:";
+
+/// Arquivo de `export_backup`. O `checksum` cobre `dados` serializado sem espaços,
+/// então reformatar o arquivo não o invalida, mas editar um valor sim.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Documento {
+    pub formato: String,
+    pub versao: u32,
+    /// `user_version` do banco exportado.
+    pub schema_version: i64,
+    pub exportado_em: String,
+    pub device_id: String,
+    pub checksum: String,
+    pub dados: Value,
+}
+
+/// `dados` do documento: as tabelas e a config que a importação aceita, chave por chave.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Dados {
+    transacoes: Vec<Transacao>,
+    recorrentes: Vec<Recorrencia>,
+    config: Map<String, Value>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResumoExportacao {
+    pub caminho: String,
+    pub transacoes: usize,
+    pub recorrentes: usize,
+    pub config: usize,
+    pub checksum: String,
+}
+
+/// O que a importação fez com os registros de uma tabela.
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Contagem {
+    pub inseridos: usize,
+    pub atualizados: usize,
+    /// Iguais aos locais ou, ao mesclar, mais antigos que eles.
+    pub mantidos: usize,
+    /// Excluídos definitivamente aqui (há lápide): não voltam pela importação.
+    pub ignorados: usize,
+    /// Só ao substituir: locais que não estavam no arquivo.
+    pub removidos: usize,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResultadoImportacao {
+    pub transacoes: Contagem,
+    pub recorrentes: Contagem,
+    /// Chaves de config importadas.
+    pub config: Vec<String>,
+    /// Chaves deste aparelho (ex: deviceId, bloqueio, servidores), que não vêm de
+    /// outra máquina.
+    pub config_ignorada: Vec<String>,
+}
+
+fn checksum(dados: &Value) -> String {
+    format!("{}{:x}", PREFIXO_CHECKSUM, Sha256::digest(dados.to_string().as_bytes()))
+}
+
+fn exportavel(key: &str) -> bool {
+    CONFIG_LIST_KEYS.contains(&key) || key == recorrencias::HORIZONTE_KEY
+}
+
+fn erro_arquivo(e: std::io::Error) -> AppError {
+    AppError::field("caminho", e.to_string())
+}
+
+// Só as chaves que `ler` importa: as deste aparelho (hash do PIN do bloqueio,
+// servidores, deviceId) não saem no arquivo. Valores de config são texto; os que
+// são JSON vão como JSON, o resto como string.
+fn config_exportada(conn: &Connection) -> Result<Map<String, Value>, AppError> {
+    let mut stmt = conn.prepare("SELECT key, value FROM config ORDER BY key")?;
+    let rows = stmt
+        .query_map([], |r| Ok((r.get::<_, String>(0)?, r.get::<_, String>(1)?)))?
+        .filter(|r| r.as_ref().map_or(true, |(k, _)| exportavel(k)));
+    let mut out = Map::new();
+    for row in rows {
+        let (key, value) = row?;
+        out.insert(key, serde_json::from_str(&value).unwrap_or(Value::String(value)));
+    }
+    Ok(out)
+}
+
+/// Monta o documento com transações, recorrências e a config compartilhável, lidos numa
+/// mesma transação para o retrato ser consistente.
+pub fn exportar(conn: &Connection) -> Result<Documento, AppError> {
+    let db_tx = conn.unchecked_transaction()?;
+    let dados = Dados {
+        transacoes: db::get_all_transacoes(&db_tx)?,
+        recorrentes: db::get_all_recorrentes(&db_tx)?,
+        config: config_exportada(&db_tx)?,
+    };
+    let dados = serde_json::to_value(dados).map_err(|e| AppError::Internal(e.to_string()))?;
+    Ok(Documento {
+        formato: FORMATO.to_string(),
+        versao: VERSAO,
+        schema_version: migrations::current_version(&db_tx)?,
+        exportado_em: db::now_iso(),
+        device_id: db::config_value(&db_tx, "deviceId"),
+        checksum: checksum(&dados),
+        dados,
+    })
+}
+
+/// Exporta para `caminho`. Grava num arquivo ao lado e renomeia, para não deixar
+/// um export pela metade por cima de outro.
+pub fn exportar_para(conn: &Connection, caminho: &Path) -> Result<ResumoExportacao, AppError> {
+    let doc = exportar(conn)?;
+    let contar = |k: &str| doc.dados.get(k).and_then(Value::as_array).map_or(0, Vec::len);
+    let resumo = ResumoExportacao {
+        caminho: caminho.display().to_string(),
+        transacoes: contar("transacoes"),
+        recorrentes: contar("recorrentes"),
+        config: doc.dados.get("config").and_then(Value::as_object).map_or(0, Map::len),
+        checksum: doc.checksum.clone(),
+    };
+    let json = serde_json::to_vec_pretty(&doc).map_err(|e| AppError::Internal(e.to_string()))?;
+    let mut parcial = caminho.as_os_str().to_owned();
+    parcial.push(".parcial");
+    std::fs::write(&parcial, json)
+        .and_then(|_| std::fs::rename(&parcial, caminho))
+        .map_err(|e| {
+            let _ = std::fs::remove_file(&parcial);
+            erro_arquivo(e)
+        })?;
+    Ok(resumo)
+}
+
+/// Arquivo de export lido e validado, pronto para `importar`.
+pub struct Importacao {
+    transacoes: Vec<Transacao>,
+    recorrentes: Vec<Recorrencia>,
+    config: Vec<(String, Value)>,
+    config_ignorada: Vec<String>,
+}
+
+// Ids repetidos na lista `lista` de `dados`, com o índice do item no arquivo.
+fn ids_repetidos(dados: &Value, lista: &str, errors: &mut ValidationErrors) {
+    let mut vistos = HashSet::new();
+    for (i, item) in dados.get(lista).and_then(Value::as_array).into_iter().flatten().enumerate() {
+        if let Some(id) = item.get("id").and_then(Value::as_str) {
+            if !vistos.insert(id) {
+                errors.add(format!("dados.{}[{}].id", lista, i), format!("id repetido: {}", id));
+            }
+        }
+    }
+}
+
+/// Lê o arquivo e confere formato, versão, checksum e cada item, sem tocar no
+/// banco. Um item inválido recusa o arquivo inteiro.
+pub fn ler(caminho: &Path) -> Result<Importacao, AppError> {
+    let texto = std::fs::read_to_string(caminho).map_err(erro_arquivo)?;
+    let doc: Documento = serde_json::from_str(&texto).map_err(|e| AppError::field("caminho", format!("JSON inválido: {}", e)))?;
+    if doc.formato != FORMATO {
+        return Err(AppError::field("formato", format!("não é um export do {}", FORMATO)));
+    }
+    if doc.versao > VERSAO {
+        return Err(AppError::field("versao", "export de uma versão mais nova do app"));
+    }
+    if doc.schema_version > migrations::latest_version() {
+        return Err(AppError::field("schemaVersion", "export de uma versão mais nova do app"));
+    }
+    if checksum(&doc.dados) != doc.checksum {
+        return Err(AppError::field("checksum", "não confere: o arquivo foi alterado ou está incompleto"));
+    }
+    let (payload, errors) = db::parse_remote(&doc.dados);
+    let mut errors = errors.prefixed("dados");
+    ids_repetidos(&doc.dados, "transacoes", &mut errors);
+    ids_repetidos(&doc.dados, "recorrentes", &mut errors);
+    let mut config: Vec<(String, Value)> = payload.config.into_iter().map(|(k, v)| (k.to_string(), v)).collect();
+    let mut config_ignorada = vec![];
+    for (key, v) in doc.dados.get("config").and_then(Value::as_object).into_iter().flatten() {
+        if key == recorrencias::HORIZONTE_KEY {
+            match Config::normalize_entry(key, v) {
+                Ok(n) => config.push((key.clone(), n)),
+                Err(e) => errors.0.extend(e.prefixed("dados.config").0),
+            }
+        } else if !exportavel(key) {
+            config_ignorada.push(key.clone());
+        }
+    }
+    errors.into_result()?;
+    Ok(Importacao { transacoes: payload.transacoes, recorrentes: payload.recorrentes, config, config_ignorada })
+}
+
+// O que a importação precisa de cada tabela.
+trait Registro: Sized {
+    const ENTIDADE: Entidade;
+    fn id(&self) -> &str;
+    fn updated_at(&self) -> Option<&str>;
+    fn local(conn: &Connection, id: &str) -> Result<Option<Self>, AppError>;
+    fn gravar(&self, conn: &Connection) -> rusqlite::Result<bool>;
+}
+
+impl Registro for Transacao {
+    const ENTIDADE: Entidade = Entidade::Transacao;
+    fn id(&self) -> &str {
+        &self.id
+    }
+    fn updated_at(&self) -> Option<&str> {
+        self.updated_at.as_deref()
+    }
+    fn local(conn: &Connection, id: &str) -> Result<Option<Self>, AppError> {
+        db::get_transacao(conn, id)
+    }
+    fn gravar(&self, conn: &Connection) -> rusqlite::Result<bool> {
+        db::write_transacao(conn, self, Escrita::Importada)
+    }
+}
+
+impl Registro for Recorrencia {
+    const ENTIDADE: Entidade = Entidade::Recorrencia;
+    fn id(&self) -> &str {
+        &self.id
+    }
+    fn updated_at(&self) -> Option<&str> {
+        self.updated_at.as_deref()
+    }
+    fn local(conn: &Connection, id: &str) -> Result<Option<Self>, AppError> {
+        db::get_recorrencia(conn, id)
+    }
+    fn gravar(&self, conn: &Connection) -> rusqlite::Result<bool> {
+        db::write_recorrencia(conn, self, Escrita::Importada)
+    }
+}
+
+// Grava os registros de uma tabela. Ao mesclar, o arquivo só vence onde o registro
+// é novo ou tem `updatedAt` mais recente que o local.
+fn importar_registros<T: Registro>(conn: &Connection, itens: &[T], modo: ModoRestauracao) -> Result<Contagem, AppError> {
+    let mut contagem = Contagem::default();
+    if modo == ModoRestauracao::Substituir {
+        let ids: HashSet<&str> = itens.iter().map(T::id).collect();
+        let locais: Vec<String> = conn
+            .prepare(&format!("SELECT id FROM {}", T::ENTIDADE.tabela()))?
+            .query_map([], |r| r.get(0))?
+            .collect::<rusqlite::Result<_>>()?;
+        // Com lápide, para a exclusão chegar também ao servidor e aos outros aparelhos
+        for velho in locais.iter().filter(|l| !ids.contains(l.as_str())) {
+            tombstones::apagar(conn, T::ENTIDADE, velho)?;
+            contagem.removidos += 1;
+        }
+    }
+    let versao = |v: Option<&str>| v.and_then(db::version_millis).unwrap_or(0);
+    for item in itens {
+        if tombstones::existe(conn, T::ENTIDADE, item.id())? {
+            contagem.ignorados += 1;
+            continue;
+        }
+        let atual = T::local(conn, item.id())?;
+        let mais_novo_aqui = atual.as_ref().is_some_and(|a| versao(a.updated_at()) >= versao(item.updated_at()));
+        if modo == ModoRestauracao::Mesclar && mais_novo_aqui || !item.gravar(conn)? {
+            contagem.mantidos += 1;
+        } else if atual.is_some() {
+            contagem.atualizados += 1;
+        } else {
+            contagem.inseridos += 1;
+        }
+    }
+    Ok(contagem)
+}
+
+/// Grava um arquivo de `export_backup` lido por `ler`. `Substituir` deixa as
+/// tabelas iguais às do arquivo; `Mesclar` junta pelo id. Tudo numa transação: se
+/// algo falhar, o banco fica como estava. O importado entra no outbox e vai no
+/// próximo push.
+pub fn importar(conn: &Connection, importacao: Importacao, modo: ModoRestauracao) -> Result<ResultadoImportacao, AppError> {
+    let db_tx = conn.unchecked_transaction()?;
+    // Recorrências antes: apagar uma tira da agenda os lançamentos previstos dela,
+    // que o arquivo traz de volta logo depois.
+    let recorrentes = importar_registros(&db_tx, &importacao.recorrentes, modo)?;
+    let transacoes = importar_registros(&db_tx, &importacao.transacoes, modo)?;
+    let mut config = vec![];
+    for (key, v) in &importacao.config {
+        db::set_config(&db_tx, key, &v.to_string())?;
+        config.push(key.clone());
+    }
+    db_tx.commit()?;
+    Ok(ResultadoImportacao { transacoes, recorrentes, config, config_ignorada: importacao.config_ignorada })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn banco() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        db::migrate(&conn).unwrap();
+        conn
+    }
+
+    fn gravar(conn: &Connection, id: &str, centavos: i64, updated_at: &str) {
+        let tx: Transacao = serde_json::from_value(json!({
+            "id": id, "date": "2026-03-10", "value": centavos, "type": "saida", "updatedAt": updated_at
+        }))
+        .unwrap();
+        db::write_transacao(conn, &tx, Escrita::Replicada).unwrap();
+    }
+
+    // Grava o documento num arquivo temporário e o lê de volta com `ler`.
+    fn ler_documento(doc: &Documento) -> Result<Importacao, AppError> {
+        let caminho = std::env::temp_dir().join(format!("vertexads-export-{}.json", uuid::Uuid::new_v4()));
+        std::fs::write(&caminho, serde_json::to_vec(doc).unwrap()).unwrap();
+        let lido = ler(&caminho);
+        let _ = std::fs::remove_file(&caminho);
+        lido
+    }
+
+    #[test]
+    fn arquivo_alterado_e_recusado_pelo_checksum() {
+        let conn = banco();
+        gravar(&conn, "t1", 100, "2026-03-10T12:00:00.000Z");
+        let mut doc = exportar(&conn).unwrap();
+        assert!(ler_documento(&doc).is_ok());
+        doc.dados["transacoes"][0]["value"] = json!(100_000);
+        match ler_documento(&doc) {
+            Err(AppError::Validation(v)) => assert_eq!(v.0[0].field, "checksum"),
+            Err(e) => panic!("esperava erro no checksum, veio {:?}", e),
+            Ok(_) => panic!("esperava erro no checksum"),
+        }
+    }
+
+    #[test]
+    fn mesclar_mantem_o_local_mais_novo() {
+        let origem = banco();
+        gravar(&origem, "t1", 100, "2026-03-10T12:00:00.000Z");
+        gravar(&origem, "t2", 200, "2026-03-10T12:00:00.000Z");
+        gravar(&origem, "t3", 300, "2026-03-10T12:00:00.000Z");
+        let importacao = ler_documento(&exportar(&origem).unwrap()).unwrap();
+
+        let destino = banco();
+        gravar(&destino, "t1", 111, "2026-03-11T09:00:00.000Z");
+        gravar(&destino, "t2", 222, "2026-03-09T09:00:00.000Z");
+        let resultado = importar(&destino, importacao, ModoRestauracao::Mesclar).unwrap();
+        let valor = |id: &str| db::get_transacao(&destino, id).unwrap().unwrap().value.centavos();
+        assert_eq!((valor("t1"), valor("t2"), valor("t3")), (111, 200, 300));
+        let c = &resultado.transacoes;
+        assert_eq!((c.mantidos, c.atualizados, c.inseridos, c.removidos), (1, 1, 1, 0));
+    }
+}
@@ -1,91 +1,247 @@
-use serde::{Deserialize, Serialize};
-use std::sync::Mutex;
-use tauri::{Manager, State};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard, OnceLock};
+use std::time::Duration;
 
+use rusqlite::Connection;
+use tauri::{AppHandle, Emitter, Manager, State};
+
+mod backups;
+mod bloqueio;
+mod conflitos;
+mod contas;
+mod credenciais;
+mod criptografia;
 mod db;
+mod diagnostico;
+mod error;
+mod exportacao;
+mod migrations;
+mod models;
+mod money;
+mod nuvem;
+mod pool;
+mod projecao;
+mod query;
+mod recorrencias;
+mod restauracao;
+mod servidores;
+mod sincronizador;
+mod stats;
+mod tombstones;
+
+use error::AppError;
+use models::{Config, Recorrencia, Transacao};
 
 struct AppState {
-    db: Mutex<Option<rusqlite::Connection>>,
+    // Vazio até o banco abrir: no startup ou, cifrado com senha, no desbloqueio
+    db: OnceLock<pool::Pool>,
+    db_path: PathBuf,
+    // Pasta do app: credenciais e a preferência de pasta de dados
+    pasta_app: PathBuf,
+    // Diagnóstico da abertura; sem banco, a mensagem dele volta nos comandos
+    inicio: Mutex<diagnostico::StatusInicio>,
+    sync: sincronizador::Sincronizador,
+    bloqueio: bloqueio::Bloqueio,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-#[allow(dead_code)]
-struct Transacao {
-    id: String,
-    date: String,
-    description: Option<String>,
-    client: Option<String>,
-    value: f64,
-    #[serde(rename = "type")]
-    tipo: String,
-    contexto: Option<String>,
-    contraparte: Option<String>,
-    category: Option<String>,
-    account: Option<String>,
-    metodo_pagamento: Option<String>,
-    status: Option<String>,
-    deleted: bool,
-    recorrencia_id: Option<String>,
-    updated_at: Option<String>,
+impl AppState {
+    fn unavailable(&self) -> AppError {
+        let Ok(inicio) = self.inicio.lock() else { return AppError::DbUnavailable("DB not open".to_string()) };
+        let mensagem = inicio.mensagem.clone().unwrap_or_else(|| "DB not open".to_string());
+        match inicio.problema {
+            Some(diagnostico::Problema::SenhaNecessaria) => AppError::DbLocked(mensagem),
+            _ => AppError::DbUnavailable(mensagem),
+        }
+    }
+
+    fn aguardando_senha(&self) -> bool {
+        self.inicio.lock().is_ok_and(|i| i.problema == Some(diagnostico::Problema::SenhaNecessaria))
+    }
+
+    // Passa a usar `p`, recém-aberto ou com o arquivo trocado: passos de startup,
+    // configuração do bloqueio e diagnóstico.
+    fn banco_aberto(&self, p: &pool::Pool, bloquear: bool) -> diagnostico::StatusInicio {
+        preparar(p);
+        if let Err(e) = p.leitura().and_then(|c| self.bloqueio.carregar(&c, bloquear)) {
+            eprintln!("[Bloqueio] {}", e);
+        }
+        let status = diagnostico::verificar(&self.db_path, p);
+        if let Ok(mut inicio) = self.inicio.lock() {
+            *inicio = status.clone();
+        }
+        status
+    }
+
+    // Banco sem passar pelo bloqueio do app: para o sync em segundo plano, o
+    // fechamento e os próprios comandos de bloqueio.
+    fn banco(&self) -> Result<&pool::Pool, AppError> {
+        self.db.get().ok_or_else(|| self.unavailable())
+    }
+
+    // Acesso dos comandos: recusa com o app bloqueado.
+    fn pool(&self) -> Result<&pool::Pool, AppError> {
+        self.bloqueio.verificar()?;
+        self.banco()
+    }
+
+    fn leitura(&self) -> Result<MutexGuard<'_, Connection>, AppError> {
+        self.pool()?.leitura()
+    }
+
+    fn escrita(&self) -> Result<MutexGuard<'_, Connection>, AppError> {
+        self.pool()?.escrita()
+    }
+
+    // Resultado de um comando que grava dados sincronizados: se gravou, agenda o push.
+    fn gravou<T>(&self, r: Result<T, AppError>) -> Result<T, AppError> {
+        if r.is_ok() {
+            self.sync.alterado();
+        }
+        r
+    }
 }
 
 #[tauri::command]
-fn get_transacoes(state: State<AppState>) -> Result<Vec<serde_json::Value>, String> {
-    let conn = state.db.lock().map_err(|e| e.to_string())?;
-    let c = conn.as_ref().ok_or("DB not open")?;
-    db::get_all_transacoes(c)
+fn get_transacoes(state: State<AppState>) -> Result<Vec<Transacao>, AppError> {
+    let c = state.leitura()?;
+    db::get_all_transacoes(&c)
 }
 
 #[tauri::command]
-fn delete_transacao(state: State<AppState>, id: String) -> Result<(), String> {
-    let conn = state.db.lock().map_err(|e| e.to_string())?;
-    let c = conn.as_ref().ok_or("DB not open")?;
-    db::delete_transacao(c, &id)
+fn query_transacoes(state: State<AppState>, query: query::TransacaoQuery) -> Result<query::TransacaoPage, AppError> {
+    let c = state.leitura()?;
+    query::query_transacoes(&c, &query)
 }
 
 #[tauri::command]
-fn put_transacao(state: State<AppState>, tx: serde_json::Value) -> Result<(), String> {
-    let conn = state.db.lock().map_err(|e| e.to_string())?;
-    let c = conn.as_ref().ok_or("DB not open")?;
-    db::put_transacao(c, tx)
+fn get_resumo_mensal(state: State<AppState>, filter: query::TransacaoFiltro) -> Result<Vec<stats::ResumoMensal>, AppError> {
+    let c = state.leitura()?;
+    stats::resumo_mensal(&c, &filter)
 }
 
 #[tauri::command]
-fn put_transacoes(state: State<AppState>, items: Vec<serde_json::Value>) -> Result<(), String> {
-    let conn = state.db.lock().map_err(|e| e.to_string())?;
-    let c = conn.as_ref().ok_or("DB not open")?;
-    for tx in items {
-        let _ = db::put_transacao(c, tx);
-    }
-    Ok(())
+fn get_estatisticas(state: State<AppState>, filter: query::TransacaoFiltro) -> Result<stats::Estatisticas, AppError> {
+    let c = state.leitura()?;
+    stats::estatisticas(&c, filter)
 }
 
 #[tauri::command]
-fn put_recorrentes(state: State<AppState>, items: Vec<serde_json::Value>) -> Result<(), String> {
-    let conn = state.db.lock().map_err(|e| e.to_string())?;
-    let c = conn.as_ref().ok_or("DB not open")?;
-    db::put_recorrentes(c, items)
+fn list_contas(state: State<AppState>) -> Result<Vec<contas::Conta>, AppError> {
+    let c = state.leitura()?;
+    contas::list_contas(&c)
 }
 
+/// Grava o cadastro da conta. Tipo e saldo inicial são deste aparelho: não vão no
+/// sync nem no export. Os outros aparelhos só recebem o nome, pelas listas de config.
 #[tauri::command]
-fn get_recorrentes(state: State<AppState>) -> Result<Vec<serde_json::Value>, String> {
-    let conn = state.db.lock().map_err(|e| e.to_string())?;
-    let c = conn.as_ref().ok_or("DB not open")?;
-    db::get_all_recorrentes(c)
+fn put_conta(state: State<AppState>, conta: contas::Conta) -> Result<(), AppError> {
+    let c = state.escrita()?;
+    contas::put_conta(&c, &conta, &db::now_iso())
 }
 
 #[tauri::command]
-fn put_recorrencia(state: State<AppState>, r: serde_json::Value) -> Result<(), String> {
-    let conn = state.db.lock().map_err(|e| e.to_string())?;
-    let c = conn.as_ref().ok_or("DB not open")?;
-    db::put_recorrencia(c, r)
+fn get_saldos(state: State<AppState>, data: Option<String>) -> Result<Vec<contas::SaldoConta>, AppError> {
+    let c = state.leitura()?;
+    contas::saldos(&c, data.as_deref())
 }
 
 #[tauri::command]
-fn get_config(state: State<AppState>) -> Result<serde_json::Value, String> {
-    let conn = state.db.lock().map_err(|e| e.to_string())?;
-    let c = conn.as_ref().ok_or("DB not open")?;
-    db::get_config(c)
+fn get_saldo_conta(state: State<AppState>, conta: String, data: Option<String>) -> Result<contas::SaldoConta, AppError> {
+    let c = state.leitura()?;
+    contas::saldo_conta(&c, &conta, data.as_deref())
+}
+
+#[tauri::command]
+fn get_serie_saldo(
+    state: State<AppState>,
+    conta: String,
+    date_from: String,
+    date_to: String,
+    granularidade: Option<contas::Granularidade>,
+) -> Result<Vec<contas::PontoSaldo>, AppError> {
+    let c = state.leitura()?;
+    contas::serie_saldo(&c, &conta, &date_from, &date_to, granularidade.unwrap_or_default())
+}
+
+#[tauri::command]
+fn project_cashflow(
+    state: State<AppState>,
+    meses: u32,
+    granularidade: Option<contas::Granularidade>,
+) -> Result<projecao::ProjecaoFluxo, AppError> {
+    let c = state.leitura()?;
+    projecao::project_cashflow(&c, meses, granularidade.unwrap_or_default())
+}
+
+#[tauri::command]
+fn delete_transacao(state: State<AppState>, id: String) -> Result<(), AppError> {
+    let c = state.escrita()?;
+    state.gravou(db::delete_transacao(&c, &id))
+}
+
+#[tauri::command]
+fn put_transacao(state: State<AppState>, tx: Transacao) -> Result<(), AppError> {
+    let c = state.escrita()?;
+    state.gravou(db::put_transacao(&c, &tx))
+}
+
+#[tauri::command]
+fn put_transacoes(state: State<AppState>, items: Vec<Transacao>) -> Result<(), AppError> {
+    let c = state.escrita()?;
+    state.gravou(db::put_transacoes(&c, &items))
+}
+
+#[tauri::command]
+fn put_recorrentes(state: State<AppState>, items: Vec<Recorrencia>) -> Result<(), AppError> {
+    let c = state.escrita()?;
+    state.gravou(db::put_recorrentes(&c, &items))
+}
+
+#[tauri::command]
+fn get_recorrentes(state: State<AppState>) -> Result<Vec<Recorrencia>, AppError> {
+    let c = state.leitura()?;
+    db::get_all_recorrentes(&c)
+}
+
+#[tauri::command]
+fn delete_recorrencia(state: State<AppState>, id: String) -> Result<(), AppError> {
+    let c = state.escrita()?;
+    state.gravou(db::delete_recorrencia(&c, &id))
+}
+
+#[tauri::command]
+fn put_recorrencia(state: State<AppState>, r: Recorrencia) -> Result<(), AppError> {
+    let c = state.escrita()?;
+    state.gravou(db::put_recorrencia(&c, &r))
+}
+
+#[tauri::command]
+fn materializar_recorrencias(state: State<AppState>, horizonte: Option<u32>) -> Result<Vec<Transacao>, AppError> {
+    let c = state.escrita()?;
+    state.gravou(recorrencias::materializar(&c, horizonte))
+}
+
+#[tauri::command]
+fn list_conflitos(state: State<AppState>) -> Result<Vec<conflitos::Conflito>, AppError> {
+    let c = state.leitura()?;
+    conflitos::list_conflitos(&c)
+}
+
+#[tauri::command]
+fn resolver_conflito(
+    state: State<AppState>,
+    entidade: models::Entidade,
+    id: String,
+    lado: conflitos::Lado,
+) -> Result<(), AppError> {
+    let c = state.escrita()?;
+    state.gravou(conflitos::resolver_conflito(&c, entidade, &id, lado))
+}
+
+#[tauri::command]
+fn get_config(state: State<AppState>) -> Result<Config, AppError> {
+    let c = state.leitura()?;
+    db::get_config(&c)
 }
 
 #[derive(serde::Deserialize)]
@@ -95,113 +251,461 @@ struct SetConfigPayload {
 }
 
 #[tauri::command]
-fn set_config(state: State<AppState>, payload: SetConfigPayload) -> Result<(), String> {
-    let conn = state.db.lock().map_err(|e| e.to_string())?;
-    let c = conn.as_ref().ok_or("DB not open")?;
-    db::set_config(c, &payload.key, &payload.value)
+fn set_config(state: State<AppState>, payload: SetConfigPayload) -> Result<(), AppError> {
+    let c = state.escrita()?;
+    state.gravou(db::set_config(&c, &payload.key, &payload.value))
+}
+
+/// Banco antigo só com valores inteiros: o frontend diz se eles ainda estão em reais
+/// (nunca passaram pela migração de lá). Devolve se converteu.
+#[tauri::command]
+fn resolver_valores_legados(state: State<AppState>, em_reais: bool) -> Result<bool, AppError> {
+    let c = state.escrita()?;
+    let convertido = db::resolver_valores_legados(&c, em_reais)?;
+    if convertido {
+        state.sync.alterado();
+    }
+    Ok(convertido)
+}
+
+#[tauri::command]
+async fn sync_pull(state: State<'_, AppState>, token: Option<String>) -> Result<(), AppError> {
+    nuvem::sync_pull(state.pool()?, token).await.map(|_| ())
+}
+
+#[tauri::command]
+fn set_auth_token(state: State<AppState>, token: Option<String>) -> Result<(), AppError> {
+    credenciais::set_token(token.as_deref())?;
+    if token.is_some_and(|t| !t.is_empty()) {
+        state.sync.sincronizar();
+    }
+    Ok(())
+}
+
+#[tauri::command]
+async fn sync_push(state: State<'_, AppState>) -> Result<(), AppError> {
+    nuvem::sync_push(state.pool()?).await
+}
+
+/// Alterações locais ainda não enviadas, para o indicador de sync.
+#[tauri::command]
+fn get_sync_pendentes(state: State<AppState>) -> Result<i64, AppError> {
+    let c = state.leitura()?;
+    Ok(db::pending_count(&c)?)
+}
+
+/// Pede um ciclo de sync agora (ex: a rede voltou), sem esperar o backoff.
+#[tauri::command]
+fn sync_agora(state: State<AppState>) {
+    state.sync.sincronizar();
+}
+
+#[tauri::command]
+fn list_servidores(state: State<AppState>) -> Result<servidores::Servidores, AppError> {
+    let c = state.leitura()?;
+    servidores::list_servidores(&c)
+}
+
+#[tauri::command]
+fn put_servidor(state: State<AppState>, perfil: servidores::PerfilServidor) -> Result<(), AppError> {
+    let c = state.escrita()?;
+    servidores::put_servidor(&c, &perfil)
+}
+
+#[tauri::command]
+fn delete_servidor(state: State<AppState>, nome: String) -> Result<(), AppError> {
+    let c = state.escrita()?;
+    servidores::delete_servidor(&c, &nome)
+}
+
+#[tauri::command]
+fn ativar_servidor(state: State<AppState>, nome: Option<String>) -> Result<(), AppError> {
+    let c = state.escrita()?;
+    servidores::ativar_servidor(&c, nome.as_deref())
+}
+
+/// Testa `url` (ex: antes de salvar o perfil) ou, sem ela, o servidor ativo.
+#[tauri::command]
+async fn check_servidor(state: State<'_, AppState>, url: Option<String>) -> Result<servidores::SaudeServidor, AppError> {
+    let url = match url {
+        Some(u) => servidores::parse_url(&u)?,
+        None => servidores::url_ativa(&*state.leitura()?),
+    };
+    Ok(nuvem::verificar_servidor(url).await)
 }
 
 #[tauri::command]
-fn sync_pull(state: State<AppState>, token: Option<String>) -> Result<(), String> {
-    let conn = state.db.lock().map_err(|e| e.to_string())?;
-    let c = conn.as_ref().ok_or("DB not open")?;
-    db::sync_pull(c, token)
+fn get_criptografia(state: State<AppState>) -> criptografia::EstadoCifra {
+    criptografia::estado(&state.db_path, state.aguardando_senha())
 }
 
+/// Abre o banco cifrado com senha. Senha errada volta `WRONG_KEY`.
 #[tauri::command]
-fn set_auth_token(state: State<AppState>, token: Option<String>) -> Result<(), String> {
-    let conn = state.db.lock().map_err(|e| e.to_string())?;
-    let c = conn.as_ref().ok_or("DB not open")?;
-    db::set_auth_token(c, token.as_deref())
+async fn desbloquear_banco(state: State<'_, AppState>, senha: String) -> Result<(), AppError> {
+    if state.db.get().is_some() {
+        return Ok(());
+    }
+    let p = pool::Pool::open(&state.db_path, Some(pool::ChaveBanco::Senha(senha)))?;
+    // Quem acabou de digitar a senha do banco não precisa digitar o PIN também
+    state.banco_aberto(&p, false);
+    if state.db.set(p).is_ok() {
+        state.sync.sincronizar();
+    }
+    Ok(())
 }
 
 #[tauri::command]
-fn sync_push(state: State<AppState>) -> Result<(), String> {
-    let conn = state.db.lock().map_err(|e| e.to_string())?;
-    let c = conn.as_ref().ok_or("DB not open")?;
-    db::sync_push(c)
+async fn proteger_banco(state: State<'_, AppState>, protecao: criptografia::Protecao) -> Result<(), AppError> {
+    criptografia::proteger(state.pool()?, &protecao)
+}
+
+#[tauri::command]
+async fn alterar_senha_banco(state: State<'_, AppState>, atual: String, nova: String) -> Result<(), AppError> {
+    criptografia::alterar_senha(state.pool()?, &atual, &nova)
+}
+
+#[tauri::command]
+fn get_bloqueio(state: State<AppState>) -> Result<bloqueio::EstadoBloqueio, AppError> {
+    state.bloqueio.estado()
+}
+
+/// Desbloqueia o app. PIN errado volta `WRONG_KEY`; errado demais, `RATE_LIMITED`.
+#[tauri::command]
+async fn desbloquear_app(state: State<'_, AppState>, segredo: String) -> Result<(), AppError> {
+    let c = state.banco()?.escrita()?;
+    state.bloqueio.desbloquear(&c, &segredo)
+}
+
+#[tauri::command]
+fn bloquear_app(state: State<AppState>) -> bool {
+    state.bloqueio.bloquear(bloqueio::Motivo::Manual)
+}
+
+/// Uso do app (teclado, mouse) visto pelo frontend; adia o bloqueio por inatividade.
+#[tauri::command]
+fn registrar_atividade(state: State<AppState>) -> Result<(), AppError> {
+    state.bloqueio.verificar()
+}
+
+/// Define, troca ou remove (`novo` vazio) o PIN ou a senha do app.
+#[tauri::command]
+async fn definir_bloqueio(state: State<'_, AppState>, atual: Option<String>, novo: Option<String>) -> Result<(), AppError> {
+    let c = state.escrita()?;
+    state.bloqueio.definir_segredo(&c, atual.as_deref(), novo.as_deref().filter(|s| !s.is_empty()))
+}
+
+#[tauri::command]
+fn put_preferencias_bloqueio(state: State<AppState>, preferencias: bloqueio::PreferenciasBloqueio) -> Result<(), AppError> {
+    let c = state.escrita()?;
+    state.bloqueio.set_preferencias(&c, preferencias)
+}
+
+#[tauri::command]
+fn get_status_inicio(state: State<AppState>) -> Result<diagnostico::StatusInicio, AppError> {
+    Ok(state.inicio.lock()?.clone())
+}
+
+// Troca o arquivo do banco (aberto ou não) e refaz o diagnóstico.
+// `senha`: só para recriar sem o banco aberto, se o atual era cifrado com senha.
+fn recuperar(
+    state: &AppState,
+    origem: Option<(&Path, Option<pool::ChaveBanco>)>,
+    senha: Option<&str>,
+) -> Result<diagnostico::StatusInicio, AppError> {
+    let guardado = match state.db.get() {
+        Some(p) => {
+            // O arquivo atual já fica guardado ao lado; o backup só vai junto da rotação
+            if let Err(e) = backups::criar_do_pool(p, backups::Motivo::Restauracao) {
+                eprintln!("[Backup] antes da recuperação: {}", e);
+            }
+            let guardado = p.substituir(origem)?;
+            state.banco_aberto(p, false);
+            Some(guardado)
+        }
+        None => {
+            let (origem, chave) = match origem {
+                Some((origem, chave)) => (Some(origem), chave),
+                None => (None, criptografia::chave_recriacao(&state.db_path, senha)?),
+            };
+            let (p, guardado) = pool::Pool::recuperar(&state.db_path, origem, chave)?;
+            state.banco_aberto(&p, false);
+            let _ = state.db.set(p);
+            guardado
+        }
+    };
+    state.sync.sincronizar();
+    let mut inicio = state.inicio.lock()?;
+    inicio.guardado_em = guardado.map(|g| g.display().to_string());
+    Ok(inicio.clone())
 }
 
+/// Passa a usar uma cópia do backup em `caminho`. O banco atual fica guardado ao
+/// lado, com o sufixo `.anterior`. `senha`: backup cifrado com senha.
 #[tauri::command]
-fn restore_from_cloud(state: State<AppState>) -> Result<(), String> {
-    let conn = state.db.lock().map_err(|e| e.to_string())?;
-    let c = conn.as_ref().ok_or("DB not open")?;
-    db::restore_from_cloud(c)
+async fn abrir_backup(state: State<'_, AppState>, caminho: String, senha: Option<String>) -> Result<diagnostico::StatusInicio, AppError> {
+    state.bloqueio.verificar()?;
+    let origem = PathBuf::from(caminho);
+    let chave = diagnostico::conferir_backup(&origem, senha, state.db.get())?;
+    recuperar(&state, Some((&origem, chave)), None)
 }
 
-fn db_path() -> std::path::PathBuf {
+/// Começa um banco vazio, com a mesma proteção do atual; o atual fica guardado ao
+/// lado. O que estava no servidor volta no próximo pull. `senha`: para o banco novo,
+/// quando o atual era cifrado com senha e não abriu.
+#[tauri::command]
+async fn recriar_banco(state: State<'_, AppState>, senha: Option<String>) -> Result<diagnostico::StatusInicio, AppError> {
+    state.bloqueio.verificar()?;
+    recuperar(&state, None, senha.as_deref())
+}
+
+/// Muda a pasta do banco (`None`: volta à padrão) e reinicia o app para abri-lo.
+/// Com `copiar`, leva uma cópia do banco atual.
+#[tauri::command]
+async fn escolher_pasta_dados(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    pasta: Option<String>,
+    copiar: bool,
+) -> Result<(), AppError> {
+    state.bloqueio.verificar()?;
+    let pasta = pasta.map(PathBuf::from);
+    diagnostico::escolher_pasta(&state.pasta_app, pasta.as_deref(), state.db.get(), copiar)?;
+    app.restart()
+}
+
+#[tauri::command]
+fn list_backups(state: State<AppState>) -> Result<Vec<backups::InfoBackup>, AppError> {
+    state.bloqueio.verificar()?;
+    backups::listar(&state.db_path)
+}
+
+#[tauri::command]
+async fn criar_backup(state: State<'_, AppState>) -> Result<backups::InfoBackup, AppError> {
+    backups::criar_do_pool(state.pool()?, backups::Motivo::Manual)
+}
+
+/// Confere um backup local sem restaurá-lo. `senha`: backup cifrado com senha.
+#[tauri::command]
+async fn verificar_backup(state: State<'_, AppState>, nome: String, senha: Option<String>) -> Result<backups::VerificacaoBackup, AppError> {
+    state.bloqueio.verificar()?;
+    backups::verificar(&state.db_path, &nome, senha, state.db.get())
+}
+
+/// Volta o banco para o backup local `nome`, como `abrir_backup`.
+#[tauri::command]
+async fn restaurar_backup(state: State<'_, AppState>, nome: String, senha: Option<String>) -> Result<diagnostico::StatusInicio, AppError> {
+    state.bloqueio.verificar()?;
+    let extraido = backups::extrair(&state.db_path, &nome)?;
+    let restaurado = diagnostico::conferir_backup(&extraido, senha, state.db.get())
+        .and_then(|chave| recuperar(&state, Some((&extraido, chave)), None));
+    backups::descartar(&extraido);
+    restaurado
+}
+
+/// Exporta transações, recorrências e toda a config para um JSON em `caminho`.
+#[tauri::command]
+async fn export_backup(state: State<'_, AppState>, caminho: String) -> Result<exportacao::ResumoExportacao, AppError> {
+    let c = state.leitura()?;
+    exportacao::exportar_para(&c, Path::new(&caminho))
+}
+
+/// Importa um JSON de `export_backup`. O arquivo é validado inteiro antes e o
+/// banco ganha um backup local antes de mudar.
+#[tauri::command]
+async fn import_backup(
+    state: State<'_, AppState>,
+    caminho: String,
+    modo: db::ModoRestauracao,
+) -> Result<exportacao::ResultadoImportacao, AppError> {
+    let importacao = exportacao::ler(Path::new(&caminho))?;
+    let p = state.pool()?;
+    backups::criar_do_pool(p, backups::Motivo::Restauracao)?;
+    let c = p.escrita()?;
+    state.gravou(exportacao::importar(&c, importacao, modo))
+}
+
+#[tauri::command]
+async fn preview_restore(state: State<'_, AppState>) -> Result<restauracao::PreviaRestauracao, AppError> {
+    let data = nuvem::fetch_snapshot(state.pool()?).await?;
+    let c = state.leitura()?;
+    restauracao::previa(&c, &data)
+}
+
+#[tauri::command]
+async fn restore_from_cloud(state: State<'_, AppState>, modo: Option<db::ModoRestauracao>) -> Result<(), AppError> {
+    nuvem::restore_from_cloud(state.pool()?, modo.unwrap_or_default()).await
+}
+
+// Bloqueio automático: avisa o frontend para trocar para a tela de PIN.
+fn bloquear_e_avisar(handle: &AppHandle, motivo: bloqueio::Motivo) {
+    if let Some(state) = handle.try_state::<AppState>() {
+        if state.bloqueio.bloquear(motivo) {
+            let _ = handle.emit("bloqueio://bloqueado", motivo);
+        }
+    }
+}
+
+fn pasta_app() -> PathBuf {
     directories::ProjectDirs::from("com", "vertexads", "financeiro")
         .map(|d| {
             let dir = d.data_dir().to_path_buf();
             let _ = std::fs::create_dir_all(&dir);
-            dir.join("vertexads.db")
+            dir
         })
-        .unwrap_or_else(|| std::path::PathBuf::from("vertexads.db"))
+        .unwrap_or_else(|| PathBuf::from("."))
+}
+
+// Na pasta escolhida pelo usuário, se houver, senão na do app.
+fn db_path(pasta_app: &Path) -> PathBuf {
+    let pasta = diagnostico::pasta_escolhida(pasta_app).unwrap_or_else(|| pasta_app.to_path_buf());
+    let _ = std::fs::create_dir_all(&pasta);
+    pasta.join(diagnostico::ARQUIVO_BANCO)
+}
+
+// Passos de startup que dependem do banco aberto.
+fn preparar(p: &pool::Pool) {
+    if let Err(e) = p.escrita().and_then(|c| credenciais::migrar_de_config(&c)) {
+        eprintln!("[Credenciais] {}", e);
+    }
+    if let Err(e) = p.escrita().and_then(|c| recorrencias::materializar(&c, None)) {
+        eprintln!("[Recorrências] {}", e);
+    }
 }
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
-    let db_path = db_path();
-    let conn = rusqlite::Connection::open(&db_path).ok();
-    if let Some(ref c) = conn {
-        let _ = db::migrate(c);
+    let pasta_app = pasta_app();
+    let db_path = db_path(&pasta_app);
+    credenciais::iniciar(&pasta_app);
+    let aberto = diagnostico::abrir(&db_path);
+    if let Err(status) = &aberto {
+        eprintln!("[DB] {:?}: {}", status.problema, status.mensagem.as_deref().unwrap_or_default());
     }
     let state = AppState {
-        db: Mutex::new(conn),
+        db: OnceLock::new(),
+        // Com o banco aberto, o status vem de banco_aberto logo abaixo
+        inicio: Mutex::new(aberto.as_ref().err().cloned().unwrap_or_default()),
+        db_path,
+        pasta_app,
+        sync: sincronizador::Sincronizador::new(),
+        bloqueio: bloqueio::Bloqueio::default(),
     };
+    if let Ok(p) = aberto {
+        state.banco_aberto(&p, true);
+        let _ = state.db.set(p);
+    }
 
     tauri::Builder::default()
         .plugin(tauri_plugin_shell::init())
         .manage(state)
         .invoke_handler(tauri::generate_handler![
             get_transacoes,
+            query_transacoes,
+            get_resumo_mensal,
+            get_estatisticas,
+            list_contas,
+            put_conta,
+            get_saldos,
+            get_saldo_conta,
+            get_serie_saldo,
+            project_cashflow,
             put_transacao,
             delete_transacao,
             put_transacoes,
             get_recorrentes,
             put_recorrencia,
+            delete_recorrencia,
             put_recorrentes,
+            materializar_recorrencias,
             get_config,
             set_config,
+            resolver_valores_legados,
             set_auth_token,
             sync_pull,
             sync_push,
+            get_sync_pendentes,
+            sync_agora,
+            list_servidores,
+            put_servidor,
+            delete_servidor,
+            ativar_servidor,
+            check_servidor,
+            get_criptografia,
+            desbloquear_banco,
+            proteger_banco,
+            alterar_senha_banco,
+            get_status_inicio,
+            abrir_backup,
+            recriar_banco,
+            escolher_pasta_dados,
+            list_backups,
+            criar_backup,
+            verificar_backup,
+            restaurar_backup,
+            export_backup,
+            import_backup,
+            get_bloqueio,
+            desbloquear_app,
+            bloquear_app,
+            registrar_atividade,
+            definir_bloqueio,
+            put_preferencias_bloqueio,
+            list_conflitos,
+            resolver_conflito,
+            preview_restore,
             restore_from_cloud,
         ])
         .setup(|app| {
             let handle = app.handle().clone();
+            let state = app.state::<AppState>();
+            state.sync.iniciar(handle.clone());
+            // O frontend também pode pedir com get_status_inicio, se ouvir depois
+            if let Ok(inicio) = state.inicio.lock() {
+                let _ = handle.emit("inicio://status", inicio.clone());
+            }
+            let vigia = handle.clone();
+            std::thread::spawn(move || loop {
+                std::thread::sleep(Duration::from_secs(15));
+                bloquear_e_avisar(&vigia, bloqueio::Motivo::Inatividade);
+            });
+            // Backup diário: confere de hora em hora, para valer também com o app aberto por dias
+            let agenda = handle.clone();
+            std::thread::spawn(move || loop {
+                if let Some(state) = agenda.try_state::<AppState>() {
+                    if let Err(e) = state.banco().and_then(backups::diario) {
+                        eprintln!("[Backup] diário: {}", e);
+                    }
+                }
+                std::thread::sleep(Duration::from_secs(60 * 60));
+            });
             if let Some(win) = app.get_webview_window("main") {
                 let win_clone = win.clone();
-                win.on_window_event(move |event| {
-                    if let tauri::WindowEvent::CloseRequested { api, .. } = event {
+                win.on_window_event(move |event| match event {
+                    tauri::WindowEvent::Focused(false) => bloquear_e_avisar(&handle, bloqueio::Motivo::Foco),
+                    tauri::WindowEvent::CloseRequested { api, .. } => {
                         api.prevent_close();
                         let handle_inner = handle.clone();
                         let win_to_close = win_clone.clone();
                         std::thread::spawn(move || {
-                            // Aguarda frontend concluir put_transacoes/put_recorrentes antes de sync
-                            std::thread::sleep(std::time::Duration::from_millis(400));
-                            const RETRIES: u32 = 3;
-                            for attempt in 0..RETRIES {
-                                if let Some(state) = handle_inner.try_state::<AppState>() {
-                                    if let Ok(guard) = state.db.lock() {
-                                        if let Some(ref conn) = *guard {
-                                            match db::sync_push(conn) {
-                                                Ok(()) => break,
-                                                Err(e) if attempt < RETRIES - 1 => {
-                                                    eprintln!("[Sync] push on close attempt {} failed: {}, retrying...", attempt + 1, e);
-                                                    std::thread::sleep(std::time::Duration::from_millis(150));
-                                                }
-                                                Err(e) => eprintln!("[Sync] push on close failed after {} attempts: {}", RETRIES, e),
-                                            }
-                                        }
-                                    }
+                            // O que foi gravado já está no outbox e sobrevive ao fechamento;
+                            // aqui só se tenta enviar agora, se houver algo pendente.
+                            if let Some(state) = handle_inner.try_state::<AppState>() {
+                                let pendentes = state
+                                    .banco()
+                                    .and_then(|p| p.leitura())
+                                    .map(|c| db::sync_habilitado(&c) && db::pending_count(&c).unwrap_or(0) > 0)
+                                    .unwrap_or(false);
+                                if pendentes {
+                                    state.sync.descarregar(std::time::Duration::from_secs(10));
                                 }
                             }
+                            // close() pediria o CloseRequested de novo e cairia aqui outra vez.
                             let _ = handle_inner.run_on_main_thread(move || {
-                                let _ = win_to_close.close();
+                                let _ = win_to_close.destroy();
                             });
                         });
                     }
+                    _ => {}
                 });
             }
             Ok(())
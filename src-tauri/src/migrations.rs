@@ -0,0 +1,533 @@
+use rusqlite::{Connection, Transaction};
+
+// Migrações numeradas do schema. A versão aplicada fica em PRAGMA user_version;
+// nunca altere uma migração já publicada, acrescente uma nova ao final da lista.
+pub struct Migration {
+    pub version: i64,
+    pub name: &'static str,
+    pub up: fn(&Transaction) -> rusqlite::Result<()>,
+}
+
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "schema_inicial",
+        up: schema_inicial,
+    },
+    Migration {
+        version: 2,
+        name: "valores_em_centavos",
+        up: valores_em_centavos,
+    },
+    Migration {
+        version: 3,
+        name: "recorrencias_completas",
+        up: recorrencias_completas,
+    },
+    Migration {
+        version: 4,
+        name: "indices_transacoes",
+        up: indices_transacoes,
+    },
+    Migration {
+        version: 5,
+        name: "contas",
+        up: contas,
+    },
+    Migration {
+        version: 6,
+        name: "tombstones",
+        up: tombstones,
+    },
+    Migration {
+        version: 7,
+        name: "change_log",
+        up: change_log,
+    },
+    Migration {
+        version: 8,
+        name: "conflitos",
+        up: conflitos,
+    },
+    Migration {
+        version: 9,
+        name: "origem_device",
+        up: origem_device,
+    },
+];
+
+#[derive(Debug)]
+pub enum MigrationError {
+    UserVersion(rusqlite::Error),
+    TooNew { found: i64, supported: i64 },
+    Step {
+        version: i64,
+        name: &'static str,
+        source: rusqlite::Error,
+    },
+}
+
+impl std::fmt::Display for MigrationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MigrationError::UserVersion(e) => write!(f, "falha ao ler a versão do schema: {}", e),
+            MigrationError::TooNew { found, supported } => write!(
+                f,
+                "banco na versão {} é mais novo que a suportada por este app ({})",
+                found, supported
+            ),
+            MigrationError::Step { version, name, source } => {
+                write!(f, "migração {} ({}) falhou: {}", version, name, source)
+            }
+        }
+    }
+}
+
+impl std::error::Error for MigrationError {}
+
+pub fn latest_version() -> i64 {
+    MIGRATIONS.last().map(|m| m.version).unwrap_or(0)
+}
+
+pub fn current_version(conn: &Connection) -> Result<i64, MigrationError> {
+    conn.query_row("PRAGMA user_version", [], |r| r.get(0))
+        .map_err(MigrationError::UserVersion)
+}
+
+/// Aplica, em ordem, as migrações ainda não aplicadas. Cada passo roda na sua
+/// própria transação junto com a atualização de user_version; se falhar, o
+/// banco permanece na versão anterior ao passo.
+pub fn run(conn: &Connection) -> Result<i64, MigrationError> {
+    let current = current_version(conn)?;
+    let supported = latest_version();
+    if current > supported {
+        return Err(MigrationError::TooNew { found: current, supported });
+    }
+    let mut applied = current;
+    for m in MIGRATIONS.iter().filter(|m| m.version > current) {
+        let step = |source| MigrationError::Step { version: m.version, name: m.name, source };
+        let tx = conn.unchecked_transaction().map_err(step)?;
+        (m.up)(&tx).map_err(step)?;
+        tx.pragma_update(None, "user_version", m.version).map_err(step)?;
+        tx.commit().map_err(step)?;
+        applied = m.version;
+    }
+    Ok(applied)
+}
+
+fn schema_inicial(tx: &Transaction) -> rusqlite::Result<()> {
+    tx.execute_batch(
+        r#"
+        CREATE TABLE IF NOT EXISTS transacoes (
+            id TEXT PRIMARY KEY,
+            data TEXT NOT NULL,
+            description TEXT,
+            client TEXT,
+            value REAL NOT NULL,
+            type TEXT NOT NULL,
+            contexto TEXT,
+            contraparte TEXT,
+            category TEXT,
+            account TEXT,
+            metodo_pagamento TEXT,
+            status TEXT,
+            deleted INTEGER NOT NULL DEFAULT 0,
+            recorrencia_id TEXT,
+            updated_at TEXT
+        );
+        CREATE TABLE IF NOT EXISTS recorrentes (
+            id TEXT PRIMARY KEY,
+            titulo TEXT,
+            valor REAL,
+            tipo TEXT,
+            categoria TEXT,
+            conta TEXT,
+            metodo_pagamento TEXT,
+            dia_vencimento INTEGER,
+            ativo INTEGER,
+            updated_at TEXT
+        );
+        CREATE TABLE IF NOT EXISTS config (
+            key TEXT PRIMARY KEY,
+            value TEXT,
+            updated_at TEXT
+        );
+        "#,
+    )
+}
+
+// Fração abaixo disso é resíduo de ponto flutuante (1999.9999999), não centavos de real.
+const RESIDUO_CENTAVOS: f64 = 0.001;
+
+/// Chave de config: banco antigo só com valores inteiros, que a migração 2 não
+/// soube dizer se estavam em reais ou centavos.
+pub const VALORES_AMBIGUOS_KEY: &str = "valoresLegadosAmbiguos";
+
+// value/valor passam de REAL para INTEGER (centavos). SQLite não altera tipo de
+// coluna, então as tabelas são recriadas. O frontend grava centavos inteiros: um
+// valor com fração (19.9) prova que o banco ainda está em reais, e aí todos os
+// valores vão vezes 100. Só com inteiros não dá para saber (centavos, ou reais
+// redondos de antes da migração do frontend): nada é convertido, ROUND só tira
+// resíduos como 1999.9999999, e a marca `VALORES_AMBIGUOS_KEY` fica para o
+// frontend, que sabe se já converteu, decidir (db::resolver_valores_legados).
+fn valores_em_centavos(tx: &Transaction) -> rusqlite::Result<()> {
+    let em_reais: bool = tx.query_row(
+        "SELECT EXISTS(SELECT 1 FROM transacoes WHERE ABS(value - ROUND(value)) > ?1)
+             OR EXISTS(SELECT 1 FROM recorrentes WHERE ABS(valor - ROUND(valor)) > ?1)",
+        [RESIDUO_CENTAVOS],
+        |r| r.get(0),
+    )?;
+    let fator = if em_reais { 100 } else { 1 };
+    let centavos = |coluna: &str| format!("CAST(ROUND({} * {}) AS INTEGER)", coluna, fator);
+    let (transacoes, recorrentes): (i64, i64) = tx.query_row(
+        "SELECT (SELECT COUNT(*) FROM transacoes), (SELECT COUNT(*) FROM recorrentes)",
+        [],
+        |r| Ok((r.get(0)?, r.get(1)?)),
+    )?;
+    if em_reais {
+        eprintln!(
+            "[DB] valores em reais: convertendo para centavos {} lançamentos e {} recorrências",
+            transacoes, recorrentes
+        );
+    } else if transacoes + recorrentes > 0 {
+        tx.execute(
+            "INSERT OR REPLACE INTO config (key, value) VALUES (?1, 'true')",
+            [VALORES_AMBIGUOS_KEY],
+        )?;
+    }
+    tx.execute_batch(&format!(
+        r#"
+        CREATE TABLE transacoes_new (
+            id TEXT PRIMARY KEY,
+            data TEXT NOT NULL,
+            description TEXT,
+            client TEXT,
+            value INTEGER NOT NULL,
+            type TEXT NOT NULL,
+            contexto TEXT,
+            contraparte TEXT,
+            category TEXT,
+            account TEXT,
+            metodo_pagamento TEXT,
+            status TEXT,
+            deleted INTEGER NOT NULL DEFAULT 0,
+            recorrencia_id TEXT,
+            updated_at TEXT
+        );
+        INSERT INTO transacoes_new
+            SELECT id, data, description, client, {value}, type, contexto, contraparte,
+                   category, account, metodo_pagamento, status, deleted, recorrencia_id, updated_at
+            FROM transacoes;
+        DROP TABLE transacoes;
+        ALTER TABLE transacoes_new RENAME TO transacoes;
+
+        CREATE TABLE recorrentes_new (
+            id TEXT PRIMARY KEY,
+            titulo TEXT,
+            valor INTEGER NOT NULL DEFAULT 0,
+            tipo TEXT,
+            categoria TEXT,
+            conta TEXT,
+            metodo_pagamento TEXT,
+            dia_vencimento INTEGER,
+            ativo INTEGER,
+            updated_at TEXT
+        );
+        INSERT INTO recorrentes_new
+            SELECT id, titulo, COALESCE({valor}, 0), tipo, categoria, conta,
+                   metodo_pagamento, dia_vencimento, ativo, updated_at
+            FROM recorrentes;
+        DROP TABLE recorrentes;
+        ALTER TABLE recorrentes_new RENAME TO recorrentes;
+        "#,
+        value = centavos("value"),
+        valor = centavos("valor"),
+    ))
+}
+
+// Campos de recorrência que o frontend já envia (frequência, janela, cliente,
+// contexto) e que antes eram descartados. Linhas sem tipo ou dia de vencimento
+// válidos, que os modelos tipados exigem, não ganham valores inventados: vão para
+// `registros_invalidos`, com os dados originais em JSON, e os ids vão para o log.
+// Contexto ou contraparte fora da lista é anulado, com o original guardado lá.
+fn recorrencias_completas(tx: &Transaction) -> rusqlite::Result<()> {
+    tx.execute_batch(
+        r#"
+        ALTER TABLE recorrentes ADD COLUMN frequencia TEXT;
+        ALTER TABLE recorrentes ADD COLUMN recorrente INTEGER NOT NULL DEFAULT 1;
+        ALTER TABLE recorrentes ADD COLUMN quantidade_meses INTEGER;
+        ALTER TABLE recorrentes ADD COLUMN data_inicio TEXT;
+        ALTER TABLE recorrentes ADD COLUMN cliente_fornecedor TEXT;
+        ALTER TABLE recorrentes ADD COLUMN contexto TEXT;
+        CREATE TABLE registros_invalidos (
+            tabela TEXT NOT NULL,
+            id TEXT NOT NULL,
+            motivo TEXT NOT NULL,
+            dados TEXT NOT NULL,
+            PRIMARY KEY (tabela, id)
+        );
+        "#,
+    )?;
+    separar_invalidos(
+        tx,
+        "recorrentes",
+        "tipo IS NULL OR tipo NOT IN ('entrada', 'saida') OR dia_vencimento IS NULL OR dia_vencimento NOT BETWEEN 1 AND 31",
+        "tipo ou dia de vencimento inválido",
+        RECORRENCIA_JSON,
+    )?;
+    separar_invalidos(tx, "transacoes", "type IS NULL OR type NOT IN ('entrada', 'saida')", "tipo inválido", TRANSACAO_JSON)?;
+    // Contexto e contraparte são opcionais: o lançamento fica, sem o valor inválido,
+    // e o original vai para registros_invalidos
+    let contexto_invalido = "contexto NOT IN ('empresa', 'pessoal') OR contraparte NOT IN ('empresa', 'pessoal')";
+    guardar_invalidos(tx, "transacoes", contexto_invalido, "contexto ou contraparte inválido, anulado", TRANSACAO_JSON)?;
+    tx.execute_batch(
+        r#"
+        UPDATE recorrentes SET ativo = 1 WHERE ativo IS NULL;
+        UPDATE transacoes SET contexto = NULL WHERE contexto NOT IN ('empresa', 'pessoal');
+        UPDATE transacoes SET contraparte = NULL WHERE contraparte NOT IN ('empresa', 'pessoal');
+        "#,
+    )
+}
+
+// Linhas como estavam antes da migração 3, para `registros_invalidos`.
+const RECORRENCIA_JSON: &str = "json_object('id', id, 'titulo', titulo, 'valor', valor, 'tipo', tipo,
+    'categoria', categoria, 'conta', conta, 'metodoPagamento', metodo_pagamento, 'diaVencimento', dia_vencimento,
+    'ativo', ativo, 'updatedAt', updated_at)";
+const TRANSACAO_JSON: &str = "json_object('id', id, 'date', data, 'description', description, 'client', client,
+    'value', value, 'type', type, 'contexto', contexto, 'contraparte', contraparte, 'category', category,
+    'account', account, 'metodoPagamento', metodo_pagamento, 'status', status, 'deleted', deleted,
+    'recorrenciaId', recorrencia_id, 'updatedAt', updated_at)";
+
+// Copia as linhas de `tabela` que casam com `filtro` para `registros_invalidos`, com
+// os ids no log. Devolve se havia alguma.
+fn guardar_invalidos(tx: &Transaction, tabela: &str, filtro: &str, motivo: &str, dados: &str) -> rusqlite::Result<bool> {
+    let ids = tx
+        .prepare(&format!("SELECT id FROM {} WHERE {}", tabela, filtro))?
+        .query_map([], |r| r.get::<_, String>(0))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    if ids.is_empty() {
+        return Ok(false);
+    }
+    eprintln!("[DB] {} com {} guardadas em registros_invalidos: {}", tabela, motivo, ids.join(", "));
+    tx.execute(
+        &format!(
+            "INSERT INTO registros_invalidos (tabela, id, motivo, dados) SELECT ?1, id, ?2, {} FROM {} WHERE {}",
+            dados, tabela, filtro
+        ),
+        [tabela, motivo],
+    )?;
+    Ok(true)
+}
+
+// Move as linhas de `tabela` que casam com `filtro` para `registros_invalidos`.
+fn separar_invalidos(tx: &Transaction, tabela: &str, filtro: &str, motivo: &str, dados: &str) -> rusqlite::Result<()> {
+    if guardar_invalidos(tx, tabela, filtro, motivo, dados)? {
+        tx.execute(&format!("DELETE FROM {} WHERE {}", tabela, filtro), [])?;
+    }
+    Ok(())
+}
+
+// Índices para os filtros e ordenações de query_transacoes e para o sync (updated_at).
+fn indices_transacoes(tx: &Transaction) -> rusqlite::Result<()> {
+    tx.execute_batch(
+        r#"
+        CREATE INDEX IF NOT EXISTS idx_transacoes_deleted_data ON transacoes (deleted, data, id);
+        CREATE INDEX IF NOT EXISTS idx_transacoes_contexto_data ON transacoes (contexto, data);
+        CREATE INDEX IF NOT EXISTS idx_transacoes_category ON transacoes (category);
+        CREATE INDEX IF NOT EXISTS idx_transacoes_account ON transacoes (account);
+        CREATE INDEX IF NOT EXISTS idx_transacoes_client ON transacoes (client);
+        CREATE INDEX IF NOT EXISTS idx_transacoes_recorrencia ON transacoes (recorrencia_id, data);
+        CREATE INDEX IF NOT EXISTS idx_transacoes_updated_at ON transacoes (updated_at);
+        "#,
+    )
+}
+
+// Contas como cadastro próprio, com saldo inicial. Popula a partir das listas de
+// config (contas/contasInvestimento) e das contas já usadas em lançamentos.
+fn contas(tx: &Transaction) -> rusqlite::Result<()> {
+    tx.execute_batch(
+        r#"
+        CREATE TABLE contas (
+            nome TEXT PRIMARY KEY,
+            tipo TEXT NOT NULL DEFAULT 'corrente',
+            saldo_inicial INTEGER NOT NULL DEFAULT 0,
+            data_saldo_inicial TEXT,
+            ativo INTEGER NOT NULL DEFAULT 1,
+            updated_at TEXT
+        );
+        INSERT OR IGNORE INTO contas (nome, tipo)
+            SELECT TRIM(j.value), 'investimento'
+            FROM config c, json_each(CASE WHEN json_valid(c.value) THEN c.value ELSE '[]' END) j
+            WHERE c.key = 'contasInvestimento' AND j.type = 'text' AND TRIM(j.value) <> '';
+        INSERT OR IGNORE INTO contas (nome, tipo)
+            SELECT TRIM(j.value), 'corrente'
+            FROM config c, json_each(CASE WHEN json_valid(c.value) THEN c.value ELSE '[]' END) j
+            WHERE c.key = 'contas' AND j.type = 'text' AND TRIM(j.value) <> '';
+        INSERT OR IGNORE INTO contas (nome)
+            SELECT DISTINCT TRIM(account) FROM transacoes WHERE account IS NOT NULL AND TRIM(account) <> '';
+        "#,
+    )
+}
+
+// Lápides de exclusões definitivas, para propagar aos outros aparelhos. `pushed`
+// marca as já enviadas ao servidor.
+fn tombstones(tx: &Transaction) -> rusqlite::Result<()> {
+    tx.execute_batch(
+        r#"
+        CREATE TABLE tombstones (
+            entidade TEXT NOT NULL,
+            id TEXT NOT NULL,
+            deleted_at TEXT NOT NULL,
+            device_id TEXT NOT NULL,
+            pushed INTEGER NOT NULL DEFAULT 0,
+            PRIMARY KEY (entidade, id)
+        );
+        "#,
+    )
+}
+
+// Registros alterados desde o último push confirmado. `seq` cresce a cada escrita;
+// o push apaga só as entradas até o maior seq enviado. Tudo que já existe entra
+// no log para o primeiro push incremental levar a base inteira.
+fn change_log(tx: &Transaction) -> rusqlite::Result<()> {
+    tx.execute_batch(
+        r#"
+        CREATE TABLE change_log (
+            entidade TEXT NOT NULL,
+            id TEXT NOT NULL,
+            seq INTEGER NOT NULL,
+            PRIMARY KEY (entidade, id)
+        );
+        CREATE INDEX idx_change_log_seq ON change_log (seq);
+        INSERT INTO change_log (entidade, id, seq) SELECT 'transacao', id, 1 FROM transacoes;
+        INSERT INTO change_log (entidade, id, seq) SELECT 'recorrencia', id, 1 FROM recorrentes;
+        INSERT INTO change_log (entidade, id, seq)
+            SELECT 'config', key, 1 FROM config
+            WHERE key IN ('categorias', 'contas', 'contasInvestimento', 'clientes', 'statusLancamento');
+        "#,
+    )
+}
+
+// `sync_base`: última versão de cada registro combinada com o servidor, base do
+// merge de três vias no pull. `conflitos`: edições concorrentes no mesmo campo,
+// aguardando o usuário escolher um lado.
+fn conflitos(tx: &Transaction) -> rusqlite::Result<()> {
+    tx.execute_batch(
+        r#"
+        CREATE TABLE sync_base (
+            entidade TEXT NOT NULL,
+            id TEXT NOT NULL,
+            dados TEXT NOT NULL,
+            PRIMARY KEY (entidade, id)
+        );
+        CREATE TABLE conflitos (
+            entidade TEXT NOT NULL,
+            id TEXT NOT NULL,
+            local TEXT NOT NULL,
+            remoto TEXT NOT NULL,
+            campos TEXT NOT NULL,
+            detectado_em TEXT NOT NULL,
+            PRIMARY KEY (entidade, id)
+        );
+        "#,
+    )
+}
+
+fn origem_device(tx: &Transaction) -> rusqlite::Result<()> {
+    tx.execute_batch(
+        r#"
+        ALTER TABLE transacoes ADD COLUMN origem_device TEXT;
+        ALTER TABLE recorrentes ADD COLUMN origem_device TEXT;
+        "#,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Banco em memória parado na versão 1, com valores ainda REAL como nos bancos antigos.
+    fn banco_v1() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        let tx = conn.unchecked_transaction().unwrap();
+        schema_inicial(&tx).unwrap();
+        tx.pragma_update(None, "user_version", 1).unwrap();
+        tx.commit().unwrap();
+        conn
+    }
+
+    fn inserir_transacao(conn: &Connection, id: &str, value: f64, tipo: &str) {
+        conn.execute(
+            "INSERT INTO transacoes (id, data, value, type) VALUES (?1, '2024-01-10', ?2, ?3)",
+            rusqlite::params![id, value, tipo],
+        )
+        .unwrap();
+    }
+
+    fn valores(conn: &Connection) -> Vec<i64> {
+        let mut stmt = conn.prepare("SELECT value FROM transacoes ORDER BY id").unwrap();
+        let rows = stmt.query_map([], |r| r.get(0)).unwrap();
+        rows.collect::<rusqlite::Result<_>>().unwrap()
+    }
+
+    #[test]
+    fn valores_em_reais_viram_centavos() {
+        let conn = banco_v1();
+        inserir_transacao(&conn, "t1", 19.9, "saida");
+        inserir_transacao(&conn, "t2", 100.0, "entrada");
+        inserir_transacao(&conn, "t3", 150000.0, "entrada");
+        conn.execute("INSERT INTO recorrentes (id, valor, tipo, dia_vencimento) VALUES ('r1', 49.9, 'saida', 5)", [])
+            .unwrap();
+        run(&conn).unwrap();
+        assert_eq!(valores(&conn), vec![1990, 10000, 15000000]);
+        let valor: i64 = conn.query_row("SELECT valor FROM recorrentes WHERE id = 'r1'", [], |r| r.get(0)).unwrap();
+        assert_eq!(valor, 4990);
+    }
+
+    #[test]
+    fn valores_ja_em_centavos_so_perdem_o_residuo() {
+        let conn = banco_v1();
+        inserir_transacao(&conn, "t1", 1990.0, "saida");
+        inserir_transacao(&conn, "t2", 1999.9999999, "saida");
+        run(&conn).unwrap();
+        assert_eq!(valores(&conn), vec![1990, 2000]);
+        let ambiguo: Option<String> =
+            conn.query_row("SELECT value FROM config WHERE key = ?1", [VALORES_AMBIGUOS_KEY], |r| r.get(0)).ok();
+        assert!(ambiguo.is_some());
+    }
+
+    #[test]
+    fn banco_ambiguo_em_reais_e_convertido_uma_vez_so() {
+        let conn = banco_v1();
+        inserir_transacao(&conn, "t1", 150.0, "saida");
+        run(&conn).unwrap();
+        assert!(crate::db::resolver_valores_legados(&conn, true).unwrap());
+        assert!(!crate::db::resolver_valores_legados(&conn, true).unwrap());
+        assert_eq!(valores(&conn), vec![15000]);
+    }
+
+    #[test]
+    fn linhas_invalidas_vao_para_registros_invalidos() {
+        let conn = banco_v1();
+        inserir_transacao(&conn, "t1", 10.0, "saida");
+        inserir_transacao(&conn, "t2", 10.0, "transferencia");
+        conn.execute("UPDATE transacoes SET contexto = 'familia' WHERE id = 't1'", []).unwrap();
+        conn.execute_batch(
+            "INSERT INTO recorrentes (id, valor, tipo, dia_vencimento) VALUES
+                ('r1', 5, 'entrada', 10), ('r2', 5, NULL, 10), ('r3', 5, 'saida', 40);",
+        )
+        .unwrap();
+        run(&conn).unwrap();
+        let mut stmt = conn.prepare("SELECT tabela || ':' || id FROM registros_invalidos ORDER BY 1").unwrap();
+        let separados: Vec<String> = stmt.query_map([], |r| r.get(0)).unwrap().collect::<rusqlite::Result<_>>().unwrap();
+        assert_eq!(separados, vec!["recorrentes:r2", "recorrentes:r3", "transacoes:t1", "transacoes:t2"]);
+        let original: String = conn
+            .query_row("SELECT json_extract(dados, '$.contexto') FROM registros_invalidos WHERE id = 't1'", [], |r| r.get(0))
+            .unwrap();
+        assert_eq!(original, "familia");
+        let restantes: i64 = conn
+            .query_row("SELECT (SELECT COUNT(*) FROM transacoes) + (SELECT COUNT(*) FROM recorrentes)", [], |r| r.get(0))
+            .unwrap();
+        assert_eq!(restantes, 2);
+    }
+}
@@ -0,0 +1,465 @@
+use rusqlite::types::{FromSql, FromSqlError, FromSqlResult, ToSql, ToSqlOutput, ValueRef};
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::Value;
+
+use crate::money::Money;
+use crate::recorrencias::HORIZONTE_MAXIMO;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TipoFluxo {
+    Entrada,
+    Saida,
+}
+
+impl TipoFluxo {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            TipoFluxo::Entrada => "entrada",
+            TipoFluxo::Saida => "saida",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "entrada" => Some(TipoFluxo::Entrada),
+            "saida" => Some(TipoFluxo::Saida),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Contexto {
+    Empresa,
+    Pessoal,
+}
+
+impl Contexto {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Contexto::Empresa => "empresa",
+            Contexto::Pessoal => "pessoal",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "empresa" => Some(Contexto::Empresa),
+            "pessoal" => Some(Contexto::Pessoal),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Frequencia {
+    Mensal,
+    Anual,
+}
+
+impl Frequencia {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Frequencia::Mensal => "mensal",
+            Frequencia::Anual => "anual",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "mensal" => Some(Frequencia::Mensal),
+            "anual" => Some(Frequencia::Anual),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TipoConta {
+    #[default]
+    Corrente,
+    Investimento,
+}
+
+impl TipoConta {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            TipoConta::Corrente => "corrente",
+            TipoConta::Investimento => "investimento",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "corrente" => Some(TipoConta::Corrente),
+            "investimento" => Some(TipoConta::Investimento),
+            _ => None,
+        }
+    }
+}
+
+/// Tipo de registro sincronizado, usado nas lápides de exclusão.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Entidade {
+    Transacao,
+    Recorrencia,
+}
+
+impl Entidade {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Entidade::Transacao => "transacao",
+            Entidade::Recorrencia => "recorrencia",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "transacao" => Some(Entidade::Transacao),
+            "recorrencia" => Some(Entidade::Recorrencia),
+            _ => None,
+        }
+    }
+
+    pub fn tabela(self) -> &'static str {
+        match self {
+            Entidade::Transacao => "transacoes",
+            Entidade::Recorrencia => "recorrentes",
+        }
+    }
+}
+
+macro_rules! sql_text_enum {
+    ($t:ty) => {
+        impl ToSql for $t {
+            fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+                Ok(ToSqlOutput::from(self.as_str()))
+            }
+        }
+
+        impl FromSql for $t {
+            fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+                let s = value.as_str()?;
+                <$t>::parse(s).ok_or_else(|| FromSqlError::Other(format!("valor inválido: {}", s).into()))
+            }
+        }
+    };
+}
+
+sql_text_enum!(TipoFluxo);
+sql_text_enum!(Contexto);
+sql_text_enum!(Frequencia);
+sql_text_enum!(TipoConta);
+sql_text_enum!(Entidade);
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FieldError {
+    pub field: String,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(transparent)]
+pub struct ValidationErrors(pub Vec<FieldError>);
+
+impl ValidationErrors {
+    pub fn add(&mut self, field: impl Into<String>, message: impl Into<String>) {
+        self.0.push(FieldError { field: field.into(), message: message.into() });
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Prefixa os campos, ex: "date" -> "items[3].date" ao validar listas.
+    pub fn prefixed(mut self, prefix: &str) -> Self {
+        for e in &mut self.0 {
+            e.field = format!("{}.{}", prefix, e.field);
+        }
+        self
+    }
+
+    pub fn into_result(self) -> Result<(), ValidationErrors> {
+        if self.is_empty() {
+            Ok(())
+        } else {
+            Err(self)
+        }
+    }
+}
+
+impl std::fmt::Display for ValidationErrors {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let parts: Vec<String> = self.0.iter().map(|e| format!("{}: {}", e.field, e.message)).collect();
+        write!(f, "{}", parts.join("; "))
+    }
+}
+
+impl std::error::Error for ValidationErrors {}
+
+fn days_in_month(year: i32, month: u32) -> u32 {
+    match month {
+        4 | 6 | 9 | 11 => 30,
+        2 if (year % 4 == 0 && year % 100 != 0) || year % 400 == 0 => 29,
+        2 => 28,
+        _ => 31,
+    }
+}
+
+/// Data no formato YYYY-MM-DD (aceita sufixo de horário ISO, ex: 2024-01-31T10:00).
+pub fn is_valid_date(s: &str) -> bool {
+    let b = s.as_bytes();
+    if b.len() < 10 || !b[..10].is_ascii() || !(b.len() == 10 || b[10] == b'T') {
+        return false;
+    }
+    is_valid_month(&s[..7])
+        && b[7] == b'-'
+        && b[8..10].iter().all(u8::is_ascii_digit)
+        && {
+            let year: i32 = s[..4].parse().unwrap_or(0);
+            let month: u32 = s[5..7].parse().unwrap_or(0);
+            let day: u32 = s[8..10].parse().unwrap_or(0);
+            day >= 1 && day <= days_in_month(year, month)
+        }
+}
+
+/// Competência no formato YYYY-MM.
+pub fn is_valid_month(s: &str) -> bool {
+    let b = s.as_bytes();
+    b.len() == 7
+        && s.is_ascii()
+        && b[..4].iter().all(u8::is_ascii_digit)
+        && b[4] == b'-'
+        && b[5..].iter().all(u8::is_ascii_digit)
+        && matches!(s[5..].parse::<u32>(), Ok(1..=12))
+}
+
+fn require_id(errors: &mut ValidationErrors, id: &str) {
+    if id.trim().is_empty() {
+        errors.add("id", "obrigatório");
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Transacao {
+    pub id: String,
+    pub date: String,
+    pub description: Option<String>,
+    pub client: Option<String>,
+    pub value: Money,
+    #[serde(rename = "type")]
+    pub tipo: TipoFluxo,
+    pub contexto: Option<Contexto>,
+    pub contraparte: Option<Contexto>,
+    pub category: Option<String>,
+    pub account: Option<String>,
+    pub metodo_pagamento: Option<String>,
+    pub status: Option<String>,
+    #[serde(default)]
+    pub deleted: bool,
+    pub recorrencia_id: Option<String>,
+    pub updated_at: Option<String>,
+    /// Aparelho que fez a última edição; o servidor e o pull o preservam.
+    #[serde(default)]
+    pub origem_device: Option<String>,
+}
+
+impl Transacao {
+    pub fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::default();
+        require_id(&mut errors, &self.id);
+        if !is_valid_date(&self.date) {
+            errors.add("date", "data inválida, use YYYY-MM-DD");
+        }
+        if self.value < Money::ZERO {
+            errors.add("value", "não pode ser negativo; use type para indicar saída");
+        }
+        if matches!(&self.status, Some(s) if s.trim().is_empty()) {
+            errors.add("status", "não pode ser vazio");
+        }
+        errors.into_result()
+    }
+}
+
+fn default_true() -> bool {
+    true
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Recorrencia {
+    pub id: String,
+    pub titulo: Option<String>,
+    pub valor: Money,
+    pub tipo: TipoFluxo,
+    pub categoria: Option<String>,
+    pub conta: Option<String>,
+    pub metodo_pagamento: Option<String>,
+    pub dia_vencimento: u32,
+    #[serde(default = "default_true")]
+    pub ativo: bool,
+    pub frequencia: Option<Frequencia>,
+    #[serde(default = "default_true")]
+    pub recorrente: bool,
+    pub quantidade_meses: Option<u32>,
+    pub data_inicio: Option<String>,
+    pub cliente_fornecedor: Option<String>,
+    pub contexto: Option<Contexto>,
+    pub updated_at: Option<String>,
+    #[serde(default)]
+    pub origem_device: Option<String>,
+}
+
+impl Recorrencia {
+    pub fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::default();
+        require_id(&mut errors, &self.id);
+        if self.valor < Money::ZERO {
+            errors.add("valor", "não pode ser negativo; use tipo para indicar saída");
+        }
+        if !(1..=31).contains(&self.dia_vencimento) {
+            errors.add("diaVencimento", "deve estar entre 1 e 31");
+        }
+        if let Some(inicio) = &self.data_inicio {
+            if !is_valid_month(inicio.get(..7).unwrap_or(inicio)) {
+                errors.add("dataInicio", "competência inválida, use YYYY-MM");
+            }
+        }
+        if !self.recorrente && matches!(self.quantidade_meses, Some(0)) {
+            errors.add("quantidadeMeses", "deve ser maior que zero");
+        }
+        errors.into_result()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Cliente {
+    pub id: String,
+    pub nome: String,
+    pub telefone: String,
+    pub endereco: String,
+}
+
+// Mesmo id estável do frontend (slugCliente) para clientes antigos salvos como string.
+fn slug_cliente(nome: &str) -> String {
+    let slug: String = nome
+        .to_lowercase()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join("_")
+        .chars()
+        .filter(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || *c == '_')
+        .collect();
+    if slug.is_empty() {
+        "c".to_string()
+    } else {
+        slug
+    }
+}
+
+impl<'de> Deserialize<'de> for Cliente {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Raw {
+            Nome(String),
+            Obj {
+                id: Option<String>,
+                nome: Option<String>,
+                telefone: Option<String>,
+                endereco: Option<String>,
+            },
+        }
+        Ok(match Raw::deserialize(deserializer)? {
+            Raw::Nome(nome) => Cliente { id: slug_cliente(&nome), nome, telefone: String::new(), endereco: String::new() },
+            Raw::Obj { id, nome, telefone, endereco } => {
+                let nome = nome.unwrap_or_default();
+                Cliente {
+                    id: id.filter(|s| !s.is_empty()).unwrap_or_else(|| slug_cliente(&nome)),
+                    nome,
+                    telefone: telefone.unwrap_or_default(),
+                    endereco: endereco.unwrap_or_default(),
+                }
+            }
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StatusLancamento {
+    pub id: String,
+    pub label: String,
+}
+
+pub const CONFIG_LIST_KEYS: [&str; 5] = ["categorias", "contas", "contasInvestimento", "clientes", "statusLancamento"];
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Config {
+    pub categorias: Vec<String>,
+    pub contas: Vec<String>,
+    pub contas_investimento: Vec<String>,
+    pub clientes: Vec<Cliente>,
+    pub status_lancamento: Vec<StatusLancamento>,
+    /// Meses à frente em que as recorrências geram lançamentos previstos.
+    pub horizonte_recorrencias: Option<u32>,
+    pub last_synced_at: Option<String>,
+}
+
+impl Config {
+    /// Valida o valor de uma das listas de configuração e devolve a forma normalizada
+    /// (ex: clientes antigos salvos como string viram objetos). Chave desconhecida
+    /// devolve o valor sem mexer.
+    pub fn normalize_entry(key: &str, value: &Value) -> Result<Value, ValidationErrors> {
+        fn parse<T: Serialize + for<'de> Deserialize<'de>>(key: &str, value: &Value) -> Result<Value, ValidationErrors> {
+            let typed: T = serde_json::from_value(value.clone()).map_err(|e| {
+                let mut errors = ValidationErrors::default();
+                errors.add(key, e.to_string());
+                errors
+            })?;
+            Ok(serde_json::to_value(typed).unwrap_or(Value::Null))
+        }
+        match key {
+            "categorias" | "contas" | "contasInvestimento" => parse::<Vec<String>>(key, value),
+            "clientes" => parse::<Vec<Cliente>>(key, value),
+            "statusLancamento" => parse::<Vec<StatusLancamento>>(key, value),
+            "horizonteRecorrencias" => match value.as_u64() {
+                Some(n) if (1..=HORIZONTE_MAXIMO as u64).contains(&n) => Ok(Value::from(n)),
+                _ => {
+                    let mut errors = ValidationErrors::default();
+                    errors.add(key, format!("deve ser um número de meses entre 1 e {}", HORIZONTE_MAXIMO));
+                    Err(errors)
+                }
+            },
+            // Chaves que o backend não conhece seguem como o frontend mandou
+            _ => Ok(value.clone()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn chave_desconhecida_passa_como_veio() {
+        let valor = json!({ "tema": "escuro", "colunas": [1, 2] });
+        assert_eq!(Config::normalize_entry("preferenciasTela", &valor).unwrap(), valor);
+    }
+
+    #[test]
+    fn listas_conhecidas_sao_validadas_e_normalizadas() {
+        let clientes = Config::normalize_entry("clientes", &json!(["Maria Souza"])).unwrap();
+        assert_eq!(clientes[0]["id"], "maria_souza");
+        assert_eq!(clientes[0]["nome"], "Maria Souza");
+        assert!(Config::normalize_entry("categorias", &json!("Aluguel")).is_err());
+        assert!(Config::normalize_entry("horizonteRecorrencias", &json!(0)).is_err());
+    }
+}
@@ -0,0 +1,136 @@
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+use serde_json::Value;
+
+use crate::backups;
+use crate::credenciais;
+use crate::db::{self, ModoRestauracao};
+use crate::error::AppError;
+use crate::pool::Pool;
+use crate::servidores::{self, SaudeServidor};
+
+const TIMEOUT_SYNC: Duration = Duration::from_secs(15);
+const TIMEOUT_SNAPSHOT: Duration = Duration::from_secs(30);
+const TIMEOUT_HEALTH: Duration = Duration::from_secs(5);
+
+// Cliente único: reaproveita conexões e TLS entre os ciclos de sync.
+static CLIENT: OnceLock<reqwest::Client> = OnceLock::new();
+
+fn client() -> Result<&'static reqwest::Client, AppError> {
+    if let Some(c) = CLIENT.get() {
+        return Ok(c);
+    }
+    let c = reqwest::Client::builder().build()?;
+    Ok(CLIENT.get_or_init(|| c))
+}
+
+fn endpoint(url: &str) -> String {
+    format!("{}/sync", url.trim_end_matches('/'))
+}
+
+async fn enviar(
+    req: reqwest::RequestBuilder,
+    token: Option<&str>,
+    context: &str,
+) -> Result<reqwest::Response, AppError> {
+    let req = match token {
+        Some(t) => req.header("Authorization", format!("Bearer {}", t)),
+        None => req,
+    };
+    let res = req.send().await?;
+    if !res.status().is_success() {
+        return Err(AppError::from_status(res.status(), context));
+    }
+    Ok(res)
+}
+
+// As funções abaixo tomam a conexão só nas etapas de banco: a rede roda sem lock,
+// e leituras e escritas locais seguem durante o sync.
+
+/// Busca e aplica as alterações do servidor desde o cursor do último pull. Devolve quantos
+/// registros vieram (lápides incluídas) e quantos foram recusados.
+pub async fn sync_pull(pool: &Pool, token_param: Option<String>) -> Result<db::ResultadoPull, AppError> {
+    let (url, since) = {
+        let conn = pool.leitura()?;
+        let url = servidores::url_ativa(&conn);
+        if url.is_empty() {
+            return Ok(db::ResultadoPull::default());
+        }
+        (url, db::config_value::<Option<u64>>(&conn, db::CURSOR_SYNC_KEY))
+    };
+    if let Some(t) = token_param.as_deref().filter(|t| !t.is_empty()) {
+        let _ = credenciais::set_token(Some(t));
+    }
+    let token = credenciais::token_valido()?;
+    let request_url = match since {
+        Some(since) => format!("{}?since={}", endpoint(&url), since),
+        None => endpoint(&url),
+    };
+    let req = client()?.get(request_url).timeout(TIMEOUT_SYNC);
+    let data: Value = enviar(req, token.as_deref(), "sync pull failed").await?.json().await?;
+    let conn = pool.escrita()?;
+    db::apply_pull(&conn, &data)
+}
+
+pub async fn sync_push(pool: &Pool) -> Result<(), AppError> {
+    let url = servidores::url_ativa(&*pool.leitura()?);
+    if url.is_empty() {
+        return Ok(());
+    }
+    let token = credenciais::token_valido()?;
+    let batch = db::push_batch(&*pool.escrita()?)?;
+    let Some(batch) = batch else { return Ok(()) };
+    let req = client()?.post(endpoint(&url)).timeout(TIMEOUT_SYNC).json(&batch.body);
+    enviar(req, token.as_deref(), "sync push failed").await?;
+    let conn = pool.escrita()?;
+    db::confirm_push(&conn, &batch)
+}
+
+/// Snapshot completo do servidor (GET /sync sem `since`), sem aplicar nada.
+pub async fn fetch_snapshot(pool: &Pool) -> Result<Value, AppError> {
+    let url = servidores::url_ativa(&*pool.leitura()?);
+    if url.is_empty() {
+        return Err(AppError::Network("Nenhum servidor de sync configurado.".to_string()));
+    }
+    let token = credenciais::token_valido()?.ok_or_else(|| {
+        AppError::Unauthorized("Token de autenticação não encontrado. Faça login primeiro.".to_string())
+    })?;
+    let req = client()?.get(endpoint(&url)).timeout(TIMEOUT_SNAPSHOT);
+    Ok(enviar(req, Some(&token), "restore failed").await?.json().await?)
+}
+
+pub async fn restore_from_cloud(pool: &Pool, modo: ModoRestauracao) -> Result<(), AppError> {
+    let data = fetch_snapshot(pool).await?;
+    // Sem o backup local, a restauração não segue: ela sobrescreve dados locais
+    backups::criar_do_pool(pool, backups::Motivo::Restauracao)?;
+    let conn = pool.escrita()?;
+    db::apply_restore(&conn, &data, modo)
+}
+
+async fn health(url: &str) -> Result<(u16, bool), AppError> {
+    let res = client()?.get(format!("{}/health", url)).timeout(TIMEOUT_HEALTH).send().await?;
+    let status = res.status();
+    // O servidor responde `{ ok: true }`; outro serviço na mesma porta não conta
+    let ok = status.is_success() && res.json::<Value>().await.ok().and_then(|v| v.get("ok")?.as_bool()).unwrap_or(false);
+    Ok((status.as_u16(), ok))
+}
+
+/// Testa a conexão com o servidor em `url` (GET /health).
+pub async fn verificar_servidor(url: String) -> SaudeServidor {
+    let inicio = Instant::now();
+    match health(&url).await {
+        Ok((status, ok)) => SaudeServidor {
+            url,
+            ok,
+            status: Some(status),
+            latencia_ms: Some(inicio.elapsed().as_millis() as u64),
+            erro: match (ok, status) {
+                (true, _) => None,
+                (false, 200..=299) => Some("Resposta inesperada de /health; a URL é de outro serviço?".to_string()),
+                (false, s) => Some(format!("Servidor retornou {}", s)),
+            },
+        },
+        Err(e) => SaudeServidor { url, ok: false, status: None, latencia_ms: None, erro: Some(e.to_string()) },
+    }
+}
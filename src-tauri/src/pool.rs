@@ -0,0 +1,401 @@
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, MutexGuard, TryLockError};
+use std::time::Duration;
+
+use rusqlite::{params, Connection, DatabaseName, ErrorCode, OpenFlags};
+
+use crate::backups;
+use crate::db;
+use crate::error::AppError;
+use crate::migrations;
+
+const LEITORES: usize = 4;
+// Quanto uma conexão espera por um lock do SQLite (ex: checkpoint do WAL) antes
+// de devolver SQLITE_BUSY.
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Chave do SQLCipher: aleatória, guardada no chaveiro do sistema (hex de 32
+/// bytes, usada direto), ou a senha do usuário (derivada pelo SQLCipher).
+#[derive(Clone, PartialEq)]
+pub enum ChaveBanco {
+    Bruta(String),
+    Senha(String),
+}
+
+impl ChaveBanco {
+    pub fn pragma(&self) -> String {
+        match self {
+            ChaveBanco::Bruta(hex) => format!("x'{}'", hex),
+            ChaveBanco::Senha(s) => s.clone(),
+        }
+    }
+}
+
+/// Conexões do banco: uma de escrita, que serializa as gravações, e algumas só de
+/// leitura. Em WAL a leitura vê o último commit e não espera escrita nem sync.
+pub struct Pool {
+    path: PathBuf,
+    chave: Mutex<Option<ChaveBanco>>,
+    escrita: Mutex<Connection>,
+    leitores: Vec<Mutex<Connection>>,
+    proximo: AtomicUsize,
+}
+
+fn abrir(path: &Path, flags: OpenFlags, chave: Option<&ChaveBanco>) -> Result<Connection, AppError> {
+    let conn = Connection::open_with_flags(path, flags)?;
+    if let Some(c) = chave {
+        conn.pragma_update(None, "key", c.pragma())?;
+    }
+    conn.busy_timeout(BUSY_TIMEOUT)?;
+    // Chave errada só aparece na primeira leitura
+    conn.query_row("SELECT count(*) FROM sqlite_master", [], |_| Ok(())).map_err(|e| match e {
+        rusqlite::Error::SqliteFailure(f, _) if f.code == ErrorCode::NotADatabase => match chave {
+            Some(_) => AppError::WrongKey("senha ou chave do banco incorreta".to_string()),
+            None => AppError::DbUnavailable(format!("{} não é um banco SQLite legível", path.display())),
+        },
+        e => e.into(),
+    })?;
+    Ok(conn)
+}
+
+// Abre a conexão de escrita em WAL, aplica as migrações e só então os leitores.
+fn conexoes(path: &Path, chave: Option<&ChaveBanco>) -> Result<(Connection, Vec<Connection>), AppError> {
+    let escrita = abrir(path, OpenFlags::default(), chave)?;
+    let modo: String = escrita.pragma_update_and_check(None, "journal_mode", "WAL", |r| r.get(0))?;
+    if !modo.eq_ignore_ascii_case("wal") {
+        eprintln!("[DB] WAL indisponível, journal_mode = {}", modo);
+    }
+    // Backup antes de migrar um banco que já tem dados; sem ele a migração segue.
+    // O schema de antes das migrações não marcava user_version: versão 0 com a
+    // tabela transacoes também é banco com dados.
+    let versao = migrations::current_version(&escrita)?;
+    let tem_dados = versao > 0
+        || escrita.query_row(
+            "SELECT EXISTS(SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'transacoes')",
+            [],
+            |r| r.get::<_, bool>(0),
+        )?;
+    if tem_dados && versao < migrations::latest_version() {
+        if let Err(e) = backups::criar(&escrita, path, chave, backups::Motivo::Migracao) {
+            eprintln!("[DB] backup antes da migração falhou: {}", e);
+        }
+    }
+    db::migrate(&escrita)?;
+    let flags = OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_URI | OpenFlags::SQLITE_OPEN_NO_MUTEX;
+    let mut leitores = Vec::with_capacity(LEITORES);
+    for _ in 0..LEITORES {
+        leitores.push(abrir(path, flags, chave)?);
+    }
+    Ok((escrita, leitores))
+}
+
+// Arquivo ao lado do banco, ex: vertexads.db-wal, vertexads.db.novo
+fn irmao(path: &Path, sufixo: &str) -> PathBuf {
+    let mut nome = path.as_os_str().to_owned();
+    nome.push(sufixo);
+    PathBuf::from(nome)
+}
+
+fn erro_io(e: std::io::Error) -> AppError {
+    AppError::DbUnavailable(format!("arquivo do banco: {}", e))
+}
+
+// Move o banco com o WAL e o índice dele, que não podem ficar para trás.
+fn mover(de: &Path, para: &Path) -> Result<(), AppError> {
+    for sufixo in ["-wal", "-shm"] {
+        let origem = irmao(de, sufixo);
+        if origem.exists() {
+            std::fs::rename(&origem, irmao(para, sufixo)).map_err(erro_io)?;
+        }
+    }
+    std::fs::rename(de, para).map_err(erro_io)
+}
+
+fn apagar(path: &Path) {
+    for p in [irmao(path, "-wal"), irmao(path, "-shm"), path.to_path_buf()] {
+        let _ = std::fs::remove_file(p);
+    }
+}
+
+// Copia o arquivo do banco, com o WAL se houver, para `destino`.
+fn copiar_arquivo(origem: &Path, destino: &Path) -> Result<(), AppError> {
+    apagar(destino);
+    let wal = irmao(origem, "-wal");
+    if wal.exists() {
+        std::fs::copy(&wal, irmao(destino, "-wal")).map_err(erro_io)?;
+    }
+    std::fs::copy(origem, destino).map(|_| ()).map_err(erro_io)
+}
+
+// Nome livre para guardar o arquivo atual numa recuperação, ex:
+// vertexads.db.20261018-101500.anterior, ou .20261018-101500-2.anterior se a
+// anterior foi no mesmo segundo.
+fn nome_guardado(path: &Path) -> PathBuf {
+    let base = chrono::Utc::now().format("%Y%m%d-%H%M%S").to_string();
+    (1..)
+        .map(|n| match n {
+            1 => irmao(path, &format!(".{}.anterior", base)),
+            n => irmao(path, &format!(".{}-{}.anterior", base, n)),
+        })
+        .find(|p| !p.exists())
+        .unwrap_or_else(|| irmao(path, ".anterior"))
+}
+
+/// Abre só para leitura, sem WAL nem migrações (ex: conferir um backup antes de
+/// usá-lo).
+pub fn abrir_leitura(path: &Path, chave: Option<&ChaveBanco>) -> Result<Connection, AppError> {
+    abrir(path, OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX, chave)
+}
+
+// Copia o banco inteiro para `destino` com outra chave (`None`: sem cifra).
+// O sqlcipher_export não leva o user_version, que é a versão do schema.
+fn exportar(conn: &Connection, destino: &Path, chave: Option<&ChaveBanco>) -> Result<(), AppError> {
+    let versao = migrations::current_version(conn)?;
+    conn.execute(
+        "ATTACH DATABASE ?1 AS destino KEY ?2",
+        params![destino.to_string_lossy(), chave.map(ChaveBanco::pragma).unwrap_or_default()],
+    )?;
+    let copia = conn
+        .query_row("SELECT sqlcipher_export('destino')", [], |_| Ok(()))
+        .and_then(|_| conn.pragma_update(Some(DatabaseName::Attached("destino")), "user_version", versao));
+    conn.execute("DETACH DATABASE destino", [])?;
+    Ok(copia?)
+}
+
+/// Regrava o banco em `origem`, que abre com `chave`, em `destino` com a chave
+/// `nova` (ex: um backup, quando o banco troca de chave).
+pub fn recifrar_arquivo(origem: &Path, chave: Option<&ChaveBanco>, destino: &Path, nova: Option<&ChaveBanco>) -> Result<(), AppError> {
+    // Leitura e escrita: num banco aberto só para leitura o ATTACH também é
+    let conn = abrir(origem, OpenFlags::default(), chave)?;
+    apagar(destino);
+    exportar(&conn, destino, nova)
+}
+
+impl Pool {
+    /// Abre o banco em WAL e aplica as migrações antes de abrir os leitores.
+    /// Banco cifrado precisa da `chave`; errada, volta `WrongKey`.
+    pub fn open(path: &Path, chave: Option<ChaveBanco>) -> Result<Pool, AppError> {
+        let (escrita, leitores) = conexoes(path, chave.as_ref())?;
+        Ok(Pool {
+            path: path.to_path_buf(),
+            chave: Mutex::new(chave),
+            escrita: Mutex::new(escrita),
+            leitores: leitores.into_iter().map(Mutex::new).collect(),
+            proximo: AtomicUsize::new(0),
+        })
+    }
+
+    /// Conexão de escrita. Enquanto o guard vive, as outras escritas esperam; não
+    /// segure através de chamadas de rede.
+    pub fn escrita(&self) -> Result<MutexGuard<'_, Connection>, AppError> {
+        Ok(self.escrita.lock()?)
+    }
+
+    /// Conexão só de leitura: a primeira livre ou, com todas ocupadas, a próxima
+    /// da fila.
+    pub fn leitura(&self) -> Result<MutexGuard<'_, Connection>, AppError> {
+        for l in &self.leitores {
+            match l.try_lock() {
+                Ok(g) => return Ok(g),
+                Err(TryLockError::WouldBlock) => {}
+                Err(TryLockError::Poisoned(e)) => return Err(e.into()),
+            }
+        }
+        let i = self.proximo.fetch_add(1, Ordering::Relaxed) % self.leitores.len();
+        Ok(self.leitores[i].lock()?)
+    }
+
+    pub fn chave_confere(&self, chave: &ChaveBanco) -> Result<bool, AppError> {
+        Ok(self.chave.lock()?.as_ref() == Some(chave))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn chave(&self) -> Result<Option<ChaveBanco>, AppError> {
+        Ok(self.chave.lock()?.clone())
+    }
+
+    /// Cópia do banco, com a mesma chave, em `destino` (ex: outra pasta de dados).
+    pub fn copiar_para(&self, destino: &Path) -> Result<(), AppError> {
+        let escrita = self.escrita()?;
+        exportar(&escrita, destino, self.chave.lock()?.as_ref())
+    }
+
+    // Prende todas as conexões, para a troca do arquivo não correr junto com
+    // leitura ou escrita.
+    fn travar(&self) -> Result<Travado<'_>, AppError> {
+        Ok(Travado {
+            escrita: self.escrita.lock()?,
+            leitores: self.leitores.iter().map(|l| l.lock()).collect::<Result<Vec<_>, _>>()?,
+            chave: self.chave.lock()?,
+        })
+    }
+
+    // Fecha as conexões, põe `novo` no lugar do banco (`None`: começa um vazio) e
+    // reabre com `nova`. O arquivo atual vai para `guardar` ou, sem ele, é apagado
+    // depois que a reabertura dá certo; se ela falhar, o arquivo atual volta.
+    fn trocar(&self, t: &mut Travado<'_>, novo: Option<&Path>, nova: Option<ChaveBanco>, guardar: Option<&Path>) -> Result<(), AppError> {
+        // Leitores antes: a última conexão a fechar, a de escrita, devolve o WAL ao
+        // arquivo (no Windows um arquivo aberto também não pode ser renomeado)
+        for l in t.leitores.iter_mut() {
+            **l = Connection::open_in_memory()?;
+        }
+        *t.escrita = Connection::open_in_memory()?;
+        let antigo = guardar.map_or_else(|| irmao(&self.path, ".antigo"), Path::to_path_buf);
+        apagar(&antigo);
+        let reaberto = mover(&self.path, &antigo)
+            .and_then(|_| novo.map_or(Ok(()), |n| mover(n, &self.path)))
+            .and_then(|_| conexoes(&self.path, nova.as_ref()));
+        let ((e, ls), resultado) = match reaberto {
+            Ok(c) => {
+                if guardar.is_none() {
+                    apagar(&antigo);
+                }
+                *t.chave = nova;
+                (c, Ok(()))
+            }
+            Err(err) => {
+                eprintln!("[DB] troca do arquivo falhou, voltando ao anterior: {}", err);
+                if antigo.exists() {
+                    apagar(&self.path);
+                    mover(&antigo, &self.path)?;
+                }
+                (conexoes(&self.path, t.chave.as_ref())?, Err(err))
+            }
+        };
+        *t.escrita = e;
+        for (l, c) in t.leitores.iter_mut().zip(ls) {
+            **l = c;
+        }
+        resultado
+    }
+
+    /// Regrava o banco com outra chave (`None`: sem cifra) e troca o arquivo sem
+    /// fechar o pool. Os backups são regravados com a chave nova junto, porque a
+    /// antiga deixa de existir (sai do chaveiro, ou era a senha trocada).
+    pub fn recifrar(&self, nova: Option<ChaveBanco>) -> Result<(), AppError> {
+        let mut t = self.travar()?;
+        let novo = irmao(&self.path, ".novo");
+        apagar(&novo);
+        exportar(&t.escrita, &novo, nova.as_ref())?;
+        // Confere a cópia antes de mexer no arquivo em uso
+        drop(abrir(&novo, OpenFlags::default(), nova.as_ref())?);
+        // Com o pool travado nenhum backup novo sai com a chave antiga no meio
+        let recifrados = backups::recifrar(&self.path, t.chave.as_ref(), nova.as_ref()).map_err(|e| {
+            apagar(&novo);
+            e
+        })?;
+        match self.trocar(&mut t, Some(&novo), nova, None) {
+            Ok(()) => {
+                backups::efetivar(recifrados);
+                Ok(())
+            }
+            Err(e) => {
+                backups::abandonar(recifrados);
+                Err(e)
+            }
+        }
+    }
+
+    /// Põe no lugar do banco uma cópia de `origem` (ex: um backup), que abre com
+    /// `chave`, ou, sem origem, um banco vazio com a chave atual. O arquivo atual
+    /// fica guardado ao lado; devolve onde.
+    pub fn substituir(&self, origem: Option<(&Path, Option<ChaveBanco>)>) -> Result<PathBuf, AppError> {
+        let mut t = self.travar()?;
+        let guardado = nome_guardado(&self.path);
+        match origem {
+            Some((origem, chave)) => {
+                let novo = irmao(&self.path, ".novo");
+                copiar_arquivo(origem, &novo)?;
+                self.trocar(&mut t, Some(&novo), chave, Some(&guardado))?;
+            }
+            None => {
+                let chave = t.chave.clone();
+                self.trocar(&mut t, None, chave, Some(&guardado))?;
+            }
+        }
+        Ok(guardado)
+    }
+
+    /// Como `substituir`, para o banco que nem chegou a abrir: guarda o arquivo
+    /// atual (se houver) ao lado e abre, com `chave`, a cópia de `origem` ou um
+    /// banco vazio. Se não abrir, o arquivo atual volta.
+    pub fn recuperar(path: &Path, origem: Option<&Path>, chave: Option<ChaveBanco>) -> Result<(Pool, Option<PathBuf>), AppError> {
+        let guardado = nome_guardado(path);
+        let tinha = path.exists();
+        if tinha {
+            mover(path, &guardado)?;
+        }
+        apagar(path);
+        let copiado = match origem {
+            Some(origem) => copiar_arquivo(origem, path),
+            None => Ok(()),
+        };
+        match copiado.and_then(|_| Pool::open(path, chave)) {
+            Ok(p) => Ok((p, tinha.then_some(guardado))),
+            Err(e) => {
+                apagar(path);
+                if tinha {
+                    mover(&guardado, path)?;
+                }
+                Err(e)
+            }
+        }
+    }
+}
+
+struct Travado<'a> {
+    escrita: MutexGuard<'a, Connection>,
+    leitores: Vec<MutexGuard<'a, Connection>>,
+    chave: MutexGuard<'a, Option<ChaveBanco>>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn banco_sem_user_version_ganha_backup_antes_de_migrar() {
+        let dir = std::env::temp_dir().join(format!("vertexads-pool-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("vertexads.db");
+        // Schema de antes das migrações (o da v1), com dados e user_version 0
+        let antigo = Connection::open(&path).unwrap();
+        let tx = antigo.unchecked_transaction().unwrap();
+        (migrations::MIGRATIONS[0].up)(&tx).unwrap();
+        tx.execute("INSERT INTO transacoes (id, data, value, type) VALUES ('t1', '2024-01-10', 19.9, 'saida')", [])
+            .unwrap();
+        tx.commit().unwrap();
+        drop(antigo);
+        let pool = Pool::open(&path, None).unwrap();
+        let lista = backups::listar(pool.path()).unwrap();
+        drop(pool);
+        let _ = std::fs::remove_dir_all(&dir);
+        assert_eq!(lista.len(), 1);
+        assert_eq!(lista[0].motivo, backups::Motivo::Migracao);
+    }
+
+    #[test]
+    fn backup_de_migracao_nao_leva_o_token_legado() {
+        let dir = std::env::temp_dir().join(format!("vertexads-pool-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("vertexads.db");
+        let antigo = Connection::open(&path).unwrap();
+        let tx = antigo.unchecked_transaction().unwrap();
+        (migrations::MIGRATIONS[0].up)(&tx).unwrap();
+        tx.execute("INSERT INTO config (key, value) VALUES ('authToken', 'token-em-texto-puro')", []).unwrap();
+        tx.commit().unwrap();
+        drop(antigo);
+        let pool = Pool::open(&path, None).unwrap();
+        let nome = backups::listar(pool.path()).unwrap().remove(0).nome;
+        let extraido = backups::extrair(pool.path(), &nome).unwrap();
+        let bytes = std::fs::read(&extraido).unwrap();
+        backups::descartar(&extraido);
+        drop(pool);
+        let _ = std::fs::remove_dir_all(&dir);
+        assert!(!bytes.windows(19).any(|w| w == b"token-em-texto-puro"));
+    }
+}
@@ -0,0 +1,215 @@
+use std::collections::{BTreeMap, HashMap};
+
+use chrono::{Duration, Months, NaiveDate};
+use rusqlite::{params, Connection};
+use serde::Serialize;
+
+use crate::contas::{self, last_day_of_month, today, Granularidade};
+use crate::error::AppError;
+use crate::models::{Contexto, TipoFluxo};
+use crate::money::Money;
+use crate::recorrencias;
+
+const MAX_MESES: u32 = 36;
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PontoProjetado {
+    pub data: String,
+    pub entradas: Money,
+    pub saidas: Money,
+    pub saldo: Money,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SerieProjetada {
+    pub chave: String,
+    pub saldo_inicial: Money,
+    pub pontos: Vec<PontoProjetado>,
+    /// Primeiro dia em que o saldo fica negativo, mesmo com granularidade mensal.
+    pub primeiro_negativo: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProjecaoFluxo {
+    pub inicio: String,
+    pub fim: String,
+    pub contas: Vec<SerieProjetada>,
+    pub contextos: Vec<SerieProjetada>,
+}
+
+// Lançamento futuro considerado na projeção; atrasados entram no saldo no primeiro dia.
+struct Movimento {
+    data: NaiveDate,
+    valor: Money,
+    tipo: TipoFluxo,
+    conta: Option<String>,
+    contexto: Option<Contexto>,
+}
+
+#[derive(Default)]
+struct Acumulador {
+    saldo: Money,
+    dias: HashMap<NaiveDate, (Money, Money)>,
+}
+
+impl Acumulador {
+    fn somar(&mut self, m: &Movimento, hoje: NaiveDate) {
+        let dia = self.dias.entry(m.data.max(hoje)).or_default();
+        match m.tipo {
+            TipoFluxo::Entrada => dia.0 += m.valor,
+            TipoFluxo::Saida => dia.1 += m.valor,
+        }
+    }
+
+    fn serie(self, chave: String, inicio: NaiveDate, fim: NaiveDate, gran: Granularidade) -> SerieProjetada {
+        let saldo_inicial = self.saldo;
+        let mut saldo = saldo_inicial;
+        let mut primeiro_negativo = None;
+        let (mut entradas, mut saidas) = (Money::ZERO, Money::ZERO);
+        let mut pontos = vec![];
+        let mut dia = inicio;
+        while dia <= fim {
+            if let Some((e, s)) = self.dias.get(&dia) {
+                entradas += *e;
+                saidas += *s;
+                saldo += *e - *s;
+            }
+            if saldo < Money::ZERO && primeiro_negativo.is_none() {
+                primeiro_negativo = Some(dia.format("%Y-%m-%d").to_string());
+            }
+            let fecha_ponto = match gran {
+                Granularidade::Diaria => true,
+                Granularidade::Mensal => dia == last_day_of_month(dia) || dia == fim,
+            };
+            if fecha_ponto {
+                pontos.push(PontoProjetado { data: dia.format("%Y-%m-%d").to_string(), entradas, saidas, saldo });
+                (entradas, saidas) = (Money::ZERO, Money::ZERO);
+            }
+            dia += Duration::days(1);
+        }
+        SerieProjetada { chave, saldo_inicial, pontos, primeiro_negativo }
+    }
+}
+
+// Tudo que ainda vai mexer no saldo: lançamentos de amanhã em diante (pagos ou não),
+// previstos atrasados (entram hoje) e recorrências ainda não geradas.
+fn movimentos(conn: &Connection, hoje: NaiveDate, fim: NaiveDate) -> Result<Vec<Movimento>, AppError> {
+    let amanha = (hoje + Duration::days(1)).format("%Y-%m-%d").to_string();
+    let depois_do_fim = (fim + Duration::days(1)).format("%Y-%m-%d").to_string();
+    let mut stmt = conn.prepare(
+        "SELECT data, value, type, account, contexto FROM transacoes
+         WHERE deleted = 0 AND data < ?2 AND (data >= ?1 OR status = 'previsto')",
+    )?;
+    let rows = stmt.query_map(params![amanha, depois_do_fim], |r| {
+        Ok((r.get::<_, String>(0)?, r.get(1)?, r.get(2)?, r.get(3)?, r.get(4)?))
+    })?;
+    let mut out = vec![];
+    for r in rows {
+        let (data, valor, tipo, conta, contexto) = r?;
+        let data = contas::parse_date("data", &data).unwrap_or(hoje);
+        out.push(Movimento { data, valor, tipo, conta, contexto });
+    }
+    for tx in recorrencias::ocorrencias_pendentes(conn, hoje, fim)? {
+        let data = contas::parse_date("data", &tx.date).unwrap_or(hoje);
+        out.push(Movimento { data, valor: tx.value, tipo: tx.tipo, conta: tx.account, contexto: tx.contexto });
+    }
+    Ok(out)
+}
+
+/// Projeta o saldo de hoje até `meses` meses à frente, por conta (a partir do saldo
+/// realizado de cada conta ativa) e por contexto (a partir do resultado realizado
+/// do contexto), somando lançamentos futuros, previstos e recorrências ativas.
+/// Lançamentos sem contexto entram nos dois contextos, como nos filtros.
+pub fn project_cashflow(conn: &Connection, meses: u32, granularidade: Granularidade) -> Result<ProjecaoFluxo, AppError> {
+    if !(1..=MAX_MESES).contains(&meses) {
+        return Err(AppError::field("meses", format!("deve estar entre 1 e {}", MAX_MESES)));
+    }
+    let hoje = today();
+    let fim = hoje.checked_add_months(Months::new(meses)).unwrap_or(hoje);
+    let movimentos = movimentos(conn, hoje, fim)?;
+
+    let mut por_conta = BTreeMap::new();
+    for conta in contas::list_contas(conn)?.into_iter().filter(|c| c.ativo) {
+        let mut acc = Acumulador { saldo: contas::saldo_em(conn, &conta, hoje)?.saldo, ..Default::default() };
+        let desde = conta.data_saldo_inicial.as_deref().map(|d| contas::parse_date("dataSaldoInicial", d)).transpose()?;
+        for m in movimentos.iter().filter(|m| m.conta.as_deref() == Some(conta.nome.as_str())) {
+            // Previsto atrasado de antes do saldo inicial já está embutido nele
+            if desde.map_or(true, |d| m.data >= d) {
+                acc.somar(m, hoje);
+            }
+        }
+        por_conta.insert(conta.nome, acc);
+    }
+
+    let mut por_contexto = BTreeMap::new();
+    for ctx in [Contexto::Empresa, Contexto::Pessoal] {
+        let saldo = conn.query_row(
+            "SELECT COALESCE(SUM(CASE type WHEN 'entrada' THEN value ELSE -value END), 0) FROM transacoes
+             WHERE deleted = 0 AND status = 'pago' AND data < ?1 AND (contexto = ?2 OR contexto IS NULL)",
+            params![(hoje + Duration::days(1)).format("%Y-%m-%d").to_string(), ctx],
+            |r| r.get(0),
+        )?;
+        let mut acc = Acumulador { saldo, ..Default::default() };
+        for m in movimentos.iter().filter(|m| m.contexto.map_or(true, |c| c == ctx)) {
+            acc.somar(m, hoje);
+        }
+        por_contexto.insert(ctx.as_str(), acc);
+    }
+
+    Ok(ProjecaoFluxo {
+        inicio: hoje.format("%Y-%m-%d").to_string(),
+        fim: fim.format("%Y-%m-%d").to_string(),
+        contas: por_conta.into_iter().map(|(k, acc)| acc.serie(k, hoje, fim, granularidade)).collect(),
+        contextos: por_contexto
+            .into_iter()
+            .map(|(k, acc)| acc.serie(k.to_string(), hoje, fim, granularidade))
+            .collect(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{self, Escrita};
+    use crate::models::Transacao;
+
+    fn inserir(conn: &Connection, id: &str, data: NaiveDate, centavos: i64, tipo: &str, status: &str) {
+        let tx: Transacao = serde_json::from_value(serde_json::json!({
+            "id": id, "date": data.format("%Y-%m-%d").to_string(), "value": centavos, "type": tipo,
+            "status": status, "account": "Banco", "contexto": "empresa"
+        }))
+        .unwrap();
+        db::write_transacao(conn, &tx, Escrita::Replicada).unwrap();
+    }
+
+    #[test]
+    fn projecao_leva_atrasado_para_hoje_e_acha_o_primeiro_negativo() {
+        let conn = Connection::open_in_memory().unwrap();
+        db::migrate(&conn).unwrap();
+        let hoje = today();
+        let conta = contas::Conta {
+            nome: "Banco".to_string(),
+            tipo: Default::default(),
+            saldo_inicial: Money::from_centavos(1000),
+            data_saldo_inicial: None,
+            ativo: true,
+            updated_at: None,
+        };
+        contas::put_conta(&conn, &conta, "2026-01-01T00:00:00.000Z").unwrap();
+        inserir(&conn, "pago", hoje - Duration::days(5), 500, "entrada", "pago");
+        inserir(&conn, "atrasado", hoje - Duration::days(1), 300, "saida", "previsto");
+        inserir(&conn, "futuro", hoje + Duration::days(10), 2000, "saida", "previsto");
+        let p = project_cashflow(&conn, 1, Granularidade::Diaria).unwrap();
+        let banco = &p.contas[0];
+        assert_eq!(banco.saldo_inicial, Money::from_centavos(1500));
+        assert_eq!(banco.pontos[0].saldo, Money::from_centavos(1200));
+        assert_eq!(banco.pontos[10].saldo, Money::from_centavos(-800));
+        assert_eq!(banco.primeiro_negativo, Some((hoje + Duration::days(10)).format("%Y-%m-%d").to_string()));
+        let empresa = p.contextos.iter().find(|s| s.chave == "empresa").unwrap();
+        assert_eq!(empresa.saldo_inicial, Money::from_centavos(500));
+        assert_eq!(empresa.pontos.last().unwrap().saldo, Money::from_centavos(-1800));
+    }
+}
@@ -0,0 +1,247 @@
+use rusqlite::types::Value as SqlValue;
+use rusqlite::{params_from_iter, Connection};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::db::{transacao_from_row, TRANSACAO_COLUMNS};
+use crate::error::AppError;
+use crate::models::{is_valid_date, Contexto, TipoFluxo, Transacao, ValidationErrors};
+
+const DEFAULT_LIMIT: u32 = 50;
+const MAX_LIMIT: u32 = 500;
+
+/// Filtros de `query_transacoes`. Campos ausentes não filtram; `deleted` ausente
+/// traz só os lançamentos ativos (fora da lixeira).
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct TransacaoFiltro {
+    pub date_from: Option<String>,
+    pub date_to: Option<String>,
+    pub contexto: Option<Contexto>,
+    #[serde(rename = "type")]
+    pub tipo: Option<TipoFluxo>,
+    pub category: Option<String>,
+    pub account: Option<String>,
+    pub client: Option<String>,
+    pub status: Option<String>,
+    pub metodo_pagamento: Option<String>,
+    pub deleted: Option<bool>,
+    pub text: Option<String>,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SortField {
+    #[default]
+    Date,
+    Value,
+    Description,
+    UpdatedAt,
+}
+
+impl SortField {
+    fn expr(self) -> &'static str {
+        match self {
+            SortField::Date => "data",
+            SortField::Value => "value",
+            SortField::Description => "COALESCE(description, '')",
+            SortField::UpdatedAt => "COALESCE(updated_at, '')",
+        }
+    }
+
+    fn key(self, tx: &Transacao) -> Value {
+        match self {
+            SortField::Date => Value::from(tx.date.clone()),
+            SortField::Value => Value::from(tx.value.centavos()),
+            SortField::Description => Value::from(tx.description.clone().unwrap_or_default()),
+            SortField::UpdatedAt => Value::from(tx.updated_at.clone().unwrap_or_default()),
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortDir {
+    Asc,
+    #[default]
+    Desc,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct TransacaoQuery {
+    pub filter: TransacaoFiltro,
+    pub sort: SortField,
+    pub direction: SortDir,
+    pub limit: Option<u32>,
+    pub cursor: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TransacaoPage {
+    pub items: Vec<Transacao>,
+    pub total: i64,
+    pub next_cursor: Option<String>,
+}
+
+// Cursor opaco para o frontend: chave de ordenação e id do último item da página.
+#[derive(Debug, Serialize, Deserialize)]
+struct Cursor {
+    sort: SortField,
+    key: Value,
+    id: String,
+}
+
+fn like_pattern(text: &str) -> String {
+    let escaped = text.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_");
+    format!("%{}%", escaped)
+}
+
+fn json_to_sql(v: &Value) -> Option<SqlValue> {
+    match v {
+        Value::String(s) => Some(SqlValue::Text(s.clone())),
+        Value::Number(n) => n.as_i64().map(SqlValue::Integer),
+        _ => None,
+    }
+}
+
+pub fn build_where(f: &TransacaoFiltro) -> Result<(Vec<String>, Vec<SqlValue>), AppError> {
+    let mut errors = ValidationErrors::default();
+    let mut clauses = vec![];
+    let mut args = vec![];
+    clauses.push("deleted = ?".to_string());
+    args.push(SqlValue::Integer(f.deleted.unwrap_or(false) as i64));
+    if let Some(from) = &f.date_from {
+        if !is_valid_date(from) {
+            errors.add("filter.dateFrom", "data inválida, use YYYY-MM-DD");
+        }
+        clauses.push("data >= ?".to_string());
+        args.push(SqlValue::Text(from.clone()));
+    }
+    if let Some(to) = &f.date_to {
+        if !is_valid_date(to) {
+            errors.add("filter.dateTo", "data inválida, use YYYY-MM-DD");
+        }
+        // Inclusivo mesmo quando `data` traz horário (2024-01-31T10:00)
+        clauses.push("data < date(?, '+1 day')".to_string());
+        args.push(SqlValue::Text(to.get(..10).unwrap_or(to).to_string()));
+    }
+    if let Some(ctx) = f.contexto {
+        // Lançamentos sem contexto aparecem nos dois, como no frontend
+        clauses.push("(contexto = ? OR contexto IS NULL)".to_string());
+        args.push(SqlValue::Text(ctx.as_str().to_string()));
+    }
+    if let Some(tipo) = f.tipo {
+        clauses.push("type = ?".to_string());
+        args.push(SqlValue::Text(tipo.as_str().to_string()));
+    }
+    for (col, v) in [
+        ("category", &f.category),
+        ("account", &f.account),
+        ("client", &f.client),
+        ("status", &f.status),
+        ("metodo_pagamento", &f.metodo_pagamento),
+    ] {
+        if let Some(v) = v {
+            clauses.push(format!("{} = ?", col));
+            args.push(SqlValue::Text(v.clone()));
+        }
+    }
+    if let Some(text) = f.text.as_deref().map(str::trim).filter(|t| !t.is_empty()) {
+        let cols = ["description", "client", "category", "account", "contraparte"];
+        let ors: Vec<String> = cols.iter().map(|c| format!("{} LIKE ? ESCAPE '\\'", c)).collect();
+        clauses.push(format!("({})", ors.join(" OR ")));
+        let pattern = like_pattern(text);
+        args.extend(cols.iter().map(|_| SqlValue::Text(pattern.clone())));
+    }
+    errors.into_result()?;
+    Ok((clauses, args))
+}
+
+/// Página de transações filtrada e ordenada no SQLite, com paginação por cursor
+/// (keyset) e o total de itens que atendem aos filtros.
+pub fn query_transacoes(conn: &Connection, q: &TransacaoQuery) -> Result<TransacaoPage, AppError> {
+    let limit = q.limit.unwrap_or(DEFAULT_LIMIT);
+    if limit == 0 || limit > MAX_LIMIT {
+        return Err(AppError::field("limit", format!("deve estar entre 1 e {}", MAX_LIMIT)));
+    }
+    let (mut clauses, mut args) = build_where(&q.filter)?;
+
+    let total: i64 = conn.query_row(
+        &format!("SELECT COUNT(*) FROM transacoes WHERE {}", clauses.join(" AND ")),
+        params_from_iter(args.iter()),
+        |r| r.get(0),
+    )?;
+
+    let (op, dir) = match q.direction {
+        SortDir::Asc => (">", "ASC"),
+        SortDir::Desc => ("<", "DESC"),
+    };
+    if let Some(raw) = &q.cursor {
+        let cursor: Cursor = serde_json::from_str(raw).map_err(|_| AppError::field("cursor", "cursor inválido"))?;
+        if cursor.sort != q.sort {
+            return Err(AppError::field("cursor", "cursor gerado para outra ordenação"));
+        }
+        let key = json_to_sql(&cursor.key).ok_or_else(|| AppError::field("cursor", "cursor inválido"))?;
+        clauses.push(format!("({}, id) {} (?, ?)", q.sort.expr(), op));
+        args.push(key);
+        args.push(SqlValue::Text(cursor.id));
+    }
+
+    let sql = format!(
+        "SELECT {} FROM transacoes WHERE {} ORDER BY {} {dir}, id {dir} LIMIT {}",
+        TRANSACAO_COLUMNS,
+        clauses.join(" AND "),
+        q.sort.expr(),
+        limit + 1,
+        dir = dir,
+    );
+    let mut stmt = conn.prepare(&sql)?;
+    let rows = stmt.query_map(params_from_iter(args.iter()), transacao_from_row)?;
+    let mut items = vec![];
+    for r in rows {
+        items.push(r?);
+    }
+    let next_cursor = if items.len() > limit as usize {
+        items.truncate(limit as usize);
+        items.last().map(|last| {
+            serde_json::to_string(&Cursor { sort: q.sort, key: q.sort.key(last), id: last.id.clone() })
+                .unwrap_or_default()
+        })
+    } else {
+        None
+    };
+    Ok(TransacaoPage { items, total, next_cursor })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{self, Escrita};
+
+    #[test]
+    fn paginas_com_datas_iguais_nao_repetem_nem_pulam() {
+        let conn = Connection::open_in_memory().unwrap();
+        db::migrate(&conn).unwrap();
+        for id in ["t3", "t1", "t5", "t2", "t4"] {
+            let tx: Transacao = serde_json::from_value(serde_json::json!({
+                "id": id, "date": "2026-03-10", "value": 1000, "type": "saida"
+            }))
+            .unwrap();
+            db::write_transacao(&conn, &tx, Escrita::Replicada).unwrap();
+        }
+        let mut q = TransacaoQuery { limit: Some(2), ..Default::default() };
+        let mut ids = vec![];
+        loop {
+            let pagina = query_transacoes(&conn, &q).unwrap();
+            assert_eq!(pagina.total, 5);
+            ids.extend(pagina.items.into_iter().map(|t| t.id));
+            match pagina.next_cursor {
+                Some(c) => q.cursor = Some(c),
+                None => break,
+            }
+        }
+        assert_eq!(ids, ["t5", "t4", "t3", "t2", "t1"]);
+    }
+}
@@ -0,0 +1,288 @@
+use chrono::{Datelike, NaiveDate};
+use rusqlite::{params, Connection};
+
+use crate::contas::today;
+use crate::db::{self, now_iso, transacao_from_row, Escrita, TRANSACAO_COLUMNS};
+use crate::error::AppError;
+use crate::models::{Entidade, Frequencia, Recorrencia, Transacao};
+use crate::tombstones;
+
+/// Chave de config com o horizonte, em meses, dos lançamentos gerados.
+pub const HORIZONTE_KEY: &str = "horizonteRecorrencias";
+pub const HORIZONTE_PADRAO: u32 = 3;
+pub const HORIZONTE_MAXIMO: u32 = 36;
+
+// Competência como número de meses (ano * 12 + mês - 1), para somar meses sem datas.
+fn mes_indice(d: NaiveDate) -> i32 {
+    d.year() * 12 + d.month0() as i32
+}
+
+fn mes_str(mes: i32) -> String {
+    format!("{:04}-{:02}", mes.div_euclid(12), mes.rem_euclid(12) + 1)
+}
+
+fn parse_mes(s: &str) -> Option<i32> {
+    NaiveDate::parse_from_str(&format!("{}-01", s.get(..7)?), "%Y-%m-%d").ok().map(mes_indice)
+}
+
+/// Vencimento da recorrência no mês; dias 29–31 caem no último dia dos meses curtos.
+pub fn data_vencimento(mes: i32, dia: u32) -> NaiveDate {
+    let (ano, m) = (mes.div_euclid(12), mes.rem_euclid(12) as u32 + 1);
+    (1..=dia.clamp(1, 31))
+        .rev()
+        .find_map(|d| NaiveDate::from_ymd_opt(ano, m, d))
+        .unwrap_or(NaiveDate::MIN)
+}
+
+// Mesma janela de handleProjetarRecorrencias: anual no mês de dataInicio, mensal a
+// partir de dataInicio e, se não recorrente, por quantidadeMeses meses. Anual sem
+// dataInicio não tem mês de referência e não gera nada.
+fn gera_no_mes(r: &Recorrencia, mes: i32) -> bool {
+    let inicio = r.data_inicio.as_deref().and_then(parse_mes);
+    match r.frequencia.unwrap_or(Frequencia::Mensal) {
+        Frequencia::Anual => inicio.is_some_and(|i| mes >= i && (mes - i) % 12 == 0),
+        Frequencia::Mensal => {
+            if inicio.is_some_and(|i| mes < i) {
+                return false;
+            }
+            match (r.recorrente, r.quantidade_meses, inicio) {
+                (false, Some(q), Some(i)) if q > 0 => mes < i + q as i32,
+                _ => true,
+            }
+        }
+    }
+}
+
+fn id_ocorrencia(r: &Recorrencia, mes: i32) -> String {
+    format!("rec_{}_{}", r.id, mes_str(mes))
+}
+
+/// Lançamento previsto da recorrência no mês. O id é determinístico, então dois
+/// aparelhos que gerem o mesmo mês produzem o mesmo registro.
+fn ocorrencia(r: &Recorrencia, mes: i32) -> Transacao {
+    Transacao {
+        id: id_ocorrencia(r, mes),
+        date: data_vencimento(mes, r.dia_vencimento).format("%Y-%m-%d").to_string(),
+        description: r.titulo.clone(),
+        client: r.cliente_fornecedor.clone(),
+        value: r.valor,
+        tipo: r.tipo,
+        contexto: r.contexto,
+        contraparte: None,
+        category: r.categoria.clone(),
+        account: r.conta.clone(),
+        metodo_pagamento: r.metodo_pagamento.clone(),
+        status: Some("previsto".to_string()),
+        deleted: false,
+        recorrencia_id: Some(r.id.clone()),
+        updated_at: None,
+        origem_device: None,
+    }
+}
+
+fn validar_horizonte(meses: u32) -> Result<u32, AppError> {
+    if (1..=HORIZONTE_MAXIMO).contains(&meses) {
+        Ok(meses)
+    } else {
+        Err(AppError::field("horizonte", format!("deve estar entre 1 e {} meses", HORIZONTE_MAXIMO)))
+    }
+}
+
+pub fn horizonte_config(conn: &Connection) -> u32 {
+    db::config_value::<Option<u32>>(conn, HORIZONTE_KEY)
+        .filter(|h| (1..=HORIZONTE_MAXIMO).contains(h))
+        .unwrap_or(HORIZONTE_PADRAO)
+}
+
+// O mês já foi gerado (aqui ou pelo frontend)? Conta qualquer lançamento da recorrência
+// no mês, inclusive na lixeira, e o id determinístico mesmo movido de mês ou excluído
+// de vez (lápide): o que o usuário apagou não volta.
+fn existe_ocorrencia(conn: &Connection, r: &Recorrencia, mes: i32) -> Result<bool, rusqlite::Error> {
+    let id = id_ocorrencia(r, mes);
+    let gerado: bool = conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM transacoes WHERE id = ?1 OR (recorrencia_id = ?2 AND data >= ?3 AND data < ?4))",
+        params![id, r.id, mes_str(mes), mes_str(mes + 1)],
+        |row| row.get(0),
+    )?;
+    Ok(gerado || tombstones::existe(conn, Entidade::Transacao, &id)?)
+}
+
+fn materializar_recorrencia(
+    conn: &Connection,
+    r: &Recorrencia,
+    horizonte: u32,
+    updated_at: &str,
+) -> Result<Vec<Transacao>, AppError> {
+    let atual = mes_indice(today());
+    let mut criados = vec![];
+    if !r.ativo {
+        return Ok(criados);
+    }
+    for mes in atual..atual + horizonte as i32 {
+        if !gera_no_mes(r, mes) || existe_ocorrencia(conn, r, mes)? {
+            continue;
+        }
+        let tx = Transacao { updated_at: Some(updated_at.to_string()), ..ocorrencia(r, mes) };
+        db::write_transacao(conn, &tx, Escrita::Local { updated_at })?;
+        criados.push(tx);
+    }
+    Ok(criados)
+}
+
+/// Gera os lançamentos previstos das recorrências ativas do mês atual até o fim do
+/// horizonte (config, se ausente). Idempotente: meses que já têm lançamento da
+/// recorrência, mesmo na lixeira ou excluído, são pulados. Devolve os lançamentos criados.
+pub fn materializar(conn: &Connection, horizonte: Option<u32>) -> Result<Vec<Transacao>, AppError> {
+    let horizonte = match horizonte {
+        Some(h) => validar_horizonte(h)?,
+        None => horizonte_config(conn),
+    };
+    let updated_at = now_iso();
+    let db_tx = conn.unchecked_transaction()?;
+    let mut criados = vec![];
+    for r in db::get_all_recorrentes(&db_tx)? {
+        criados.extend(materializar_recorrencia(&db_tx, &r, horizonte, &updated_at)?);
+    }
+    db_tx.commit()?;
+    Ok(criados)
+}
+
+/// Ocorrências ainda não geradas das recorrências ativas nos meses de `de` até `ate`,
+/// com vencimento até `ate`, para projeções além do que já foi materializado.
+pub fn ocorrencias_pendentes(conn: &Connection, de: NaiveDate, ate: NaiveDate) -> Result<Vec<Transacao>, AppError> {
+    let limite = ate.format("%Y-%m-%d").to_string();
+    let mut out = vec![];
+    for r in db::get_all_recorrentes(conn)?.iter().filter(|r| r.ativo) {
+        for mes in mes_indice(de)..=mes_indice(ate) {
+            if !gera_no_mes(r, mes) || existe_ocorrencia(conn, r, mes)? {
+                continue;
+            }
+            let tx = ocorrencia(r, mes);
+            if tx.date <= limite {
+                out.push(tx);
+            }
+        }
+    }
+    Ok(out)
+}
+
+/// A edição de `antiga` para `nova` muda os lançamentos previstos: valor, vencimento,
+/// conta, se está ativa ou a janela de meses em que gera.
+pub fn altera_ocorrencias(antiga: &Recorrencia, nova: &Recorrencia) -> bool {
+    antiga.valor != nova.valor
+        || antiga.dia_vencimento != nova.dia_vencimento
+        || antiga.conta != nova.conta
+        || antiga.ativo != nova.ativo
+        || antiga.frequencia != nova.frequencia
+        || antiga.recorrente != nova.recorrente
+        || antiga.quantidade_meses != nova.quantidade_meses
+        || antiga.data_inicio != nova.data_inicio
+}
+
+/// Aplica a edição de uma recorrência aos lançamentos dela ainda previstos a partir
+/// de hoje: valor, descrição, conta, vencimento etc. passam a seguir a recorrência, e
+/// os que saíram da janela (ou todos, se foi desativada) vão para a lixeira. Pagos e
+/// passados não mudam. Em seguida gera os meses que faltarem no horizonte.
+/// Deve rodar dentro da transação de quem grava a recorrência.
+pub fn propagar_alteracao(conn: &Connection, r: &Recorrencia, updated_at: &str) -> Result<(), AppError> {
+    let hoje = today().format("%Y-%m-%d").to_string();
+    let sql = format!(
+        "SELECT {} FROM transacoes WHERE recorrencia_id = ?1 AND deleted = 0 AND status = 'previsto' AND data >= ?2",
+        TRANSACAO_COLUMNS
+    );
+    let mut stmt = conn.prepare(&sql)?;
+    let futuras = stmt
+        .query_map(params![r.id, hoje], transacao_from_row)?
+        .collect::<Result<Vec<_>, _>>()?;
+    for tx in futuras {
+        let Some(mes) = parse_mes(&tx.date) else { continue };
+        let nova = if r.ativo && gera_no_mes(r, mes) {
+            let base = ocorrencia(r, mes);
+            Transacao {
+                id: tx.id.clone(),
+                contexto: base.contexto.or(tx.contexto),
+                contraparte: tx.contraparte,
+                updated_at: tx.updated_at.clone(),
+                ..base
+            }
+        } else {
+            Transacao { deleted: true, ..tx.clone() }
+        };
+        if nova != tx {
+            db::write_transacao(conn, &nova, Escrita::Local { updated_at })?;
+        }
+    }
+    materializar_recorrencia(conn, r, horizonte_config(conn), updated_at)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::money::Money;
+
+    fn mes(ano: i32, m: u32) -> i32 {
+        mes_indice(NaiveDate::from_ymd_opt(ano, m, 1).unwrap())
+    }
+
+    fn dia(ano: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(ano, m, d).unwrap()
+    }
+
+    #[test]
+    fn vencimento_no_dia_31_cai_no_ultimo_dia_do_mes() {
+        assert_eq!(data_vencimento(mes(2026, 2), 31), dia(2026, 2, 28));
+        assert_eq!(data_vencimento(mes(2028, 2), 31), dia(2028, 2, 29));
+        assert_eq!(data_vencimento(mes(2026, 4), 31), dia(2026, 4, 30));
+        assert_eq!(data_vencimento(mes(2026, 1), 31), dia(2026, 1, 31));
+    }
+
+    fn banco_com_recorrencia() -> (Connection, Recorrencia) {
+        let conn = Connection::open_in_memory().unwrap();
+        db::migrate(&conn).unwrap();
+        let r: Recorrencia =
+            serde_json::from_value(serde_json::json!({ "id": "r1", "valor": 500, "tipo": "saida", "diaVencimento": 31 }))
+                .unwrap();
+        db::put_recorrencia(&conn, &r).unwrap();
+        (conn, r)
+    }
+
+    #[test]
+    fn ocorrencia_na_lixeira_ou_excluida_nao_volta() {
+        let (conn, r) = banco_com_recorrencia();
+        let (fev, mar) = (mes(2026, 2), mes(2026, 3));
+        assert!(!existe_ocorrencia(&conn, &r, fev).unwrap());
+        let na_lixeira = Transacao { deleted: true, ..ocorrencia(&r, fev) };
+        db::write_transacao(&conn, &na_lixeira, Escrita::Replicada).unwrap();
+        assert!(existe_ocorrencia(&conn, &r, fev).unwrap());
+        conn.execute(
+            "INSERT INTO tombstones (entidade, id, deleted_at, device_id, pushed) VALUES ('transacao', ?1, ?2, 'd1', 1)",
+            params![id_ocorrencia(&r, mar), now_iso()],
+        )
+        .unwrap();
+        assert!(existe_ocorrencia(&conn, &r, mar).unwrap());
+    }
+
+    #[test]
+    fn regravar_recorrencia_igual_preserva_ocorrencia_editada() {
+        let (conn, r) = banco_com_recorrencia();
+        let id = id_ocorrencia(&r, mes_indice(today()));
+        let gerada = db::get_transacao(&conn, &id).unwrap().unwrap();
+        let editada = Transacao { value: Money::from_centavos(750), ..gerada };
+        db::put_transacao(&conn, &editada).unwrap();
+        db::put_recorrentes(&conn, &[Recorrencia { titulo: Some("Aluguel".to_string()), ..r.clone() }]).unwrap();
+        assert_eq!(db::get_transacao(&conn, &id).unwrap().unwrap().value, Money::from_centavos(750));
+        db::put_recorrencia(&conn, &Recorrencia { valor: Money::from_centavos(600), ..r }).unwrap();
+        assert_eq!(db::get_transacao(&conn, &id).unwrap().unwrap().value, Money::from_centavos(600));
+    }
+
+    #[test]
+    fn horizonte_nao_vai_no_push() {
+        let (conn, _) = banco_com_recorrencia();
+        let pendentes = db::pending_count(&conn).unwrap();
+        db::set_config(&conn, HORIZONTE_KEY, "12").unwrap();
+        assert_eq!(horizonte_config(&conn), 12);
+        assert!(!db::is_pending(&conn, "config", HORIZONTE_KEY).unwrap());
+        assert_eq!(db::pending_count(&conn).unwrap(), pendentes);
+    }
+}
@@ -0,0 +1,100 @@
+use std::collections::HashMap;
+
+use rusqlite::Connection;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::conflitos;
+use crate::db::{self, RemotePayload};
+use crate::error::AppError;
+use crate::models::{Recorrencia, Transacao, ValidationErrors};
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Divergencia<T> {
+    pub local: T,
+    pub remoto: T,
+    pub campos: Vec<String>,
+}
+
+/// Registros de uma tabela comparados por id entre o banco local e a nuvem.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiffRegistros<T> {
+    pub somente_local: Vec<T>,
+    pub somente_remoto: Vec<T>,
+    pub diferentes: Vec<Divergencia<T>>,
+}
+
+/// Itens de uma lista de config presentes só de um dos lados.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiffLista {
+    pub chave: String,
+    pub somente_local: Vec<Value>,
+    pub somente_remoto: Vec<Value>,
+}
+
+/// O que um restore com `Substituir` faria: `somenteLocal` é o que se perde.
+/// `rejeitados` lista itens inválidos da nuvem; com algum, o restore é recusado.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PreviaRestauracao {
+    pub transacoes: DiffRegistros<Transacao>,
+    pub recorrentes: DiffRegistros<Recorrencia>,
+    pub config: Vec<DiffLista>,
+    pub rejeitados: ValidationErrors,
+}
+
+fn comparar_registros<T: Serialize + Clone>(
+    locais: Vec<T>,
+    remotos: &[T],
+    id: impl Fn(&T) -> &str,
+) -> DiffRegistros<T> {
+    let json = |v: &T| serde_json::to_value(v).unwrap_or_default();
+    let por_id: HashMap<&str, &T> = remotos.iter().map(|r| (id(r), r)).collect();
+    let mut diff = DiffRegistros { somente_local: vec![], somente_remoto: vec![], diferentes: vec![] };
+    for local in &locais {
+        match por_id.get(id(local)) {
+            None => diff.somente_local.push(local.clone()),
+            Some(remoto) => {
+                let campos = conflitos::campos_diferentes(&json(local), &json(remoto));
+                if !campos.is_empty() {
+                    diff.diferentes.push(Divergencia { local: local.clone(), remoto: (*remoto).clone(), campos });
+                }
+            }
+        }
+    }
+    let ids_locais: Vec<&str> = locais.iter().map(&id).collect();
+    diff.somente_remoto = remotos.iter().filter(|r| !ids_locais.contains(&id(r))).cloned().collect();
+    diff
+}
+
+// Listas que não vêm na nuvem não são tocadas pelo restore e ficam fora da prévia.
+fn comparar_config(conn: &Connection, remotas: &[(&'static str, Value)]) -> Vec<DiffLista> {
+    let mut out = vec![];
+    for (chave, remota) in remotas {
+        let local: Value = db::config_value(conn, chave);
+        let (local, remota) = (local.as_array().cloned().unwrap_or_default(), remota.as_array().cloned().unwrap_or_default());
+        let diff = DiffLista {
+            chave: chave.to_string(),
+            somente_local: local.iter().filter(|v| !remota.contains(v)).cloned().collect(),
+            somente_remoto: remota.iter().filter(|v| !local.contains(v)).cloned().collect(),
+        };
+        if !diff.somente_local.is_empty() || !diff.somente_remoto.is_empty() {
+            out.push(diff);
+        }
+    }
+    out
+}
+
+/// Compara o snapshot da nuvem com o banco local sem gravar nada.
+pub fn previa(conn: &Connection, data: &Value) -> Result<PreviaRestauracao, AppError> {
+    let (remoto, rejeitados): (RemotePayload, _) = db::parse_remote(data);
+    Ok(PreviaRestauracao {
+        transacoes: comparar_registros(db::get_all_transacoes(conn)?, &remoto.transacoes, |t| t.id.as_str()),
+        recorrentes: comparar_registros(db::get_all_recorrentes(conn)?, &remoto.recorrentes, |r| r.id.as_str()),
+        config: comparar_config(conn, &remoto.config),
+        rejeitados,
+    })
+}
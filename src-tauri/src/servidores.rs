@@ -0,0 +1,183 @@
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+
+use crate::credenciais;
+use crate::db;
+use crate::error::AppError;
+use crate::models::ValidationErrors;
+
+const PERFIS_KEY: &str = "servidores";
+const ATIVO_KEY: &str = "servidorAtivo";
+
+/// Servidor de sync cadastrado neste aparelho. Os perfis ficam só no banco local,
+/// fora do sync: cada instalação aponta para o seu.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PerfilServidor {
+    pub nome: String,
+    pub url: String,
+}
+
+impl PerfilServidor {
+    pub fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::default();
+        if self.nome.trim().is_empty() {
+            errors.add("nome", "obrigatório");
+        }
+        if let Err(m) = validar_url(&self.url) {
+            errors.add("url", m);
+        }
+        errors.into_result()
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Servidores {
+    pub perfis: Vec<PerfilServidor>,
+    /// Nome do perfil em uso; sem perfil ativo vale `padrao`.
+    pub ativo: Option<String>,
+    /// URL embutida no build, usada enquanto nenhum perfil foi ativado.
+    pub padrao: String,
+    /// URL usada de fato por pull, push e restore.
+    pub url: String,
+}
+
+/// Resultado de GET /health. Falha de conexão não é erro do comando: volta com
+/// `ok: false` e a mensagem em `erro`, para a tela de configuração mostrar.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SaudeServidor {
+    pub url: String,
+    pub ok: bool,
+    pub status: Option<u16>,
+    pub latencia_ms: Option<u64>,
+    pub erro: Option<String>,
+}
+
+fn validar_url(url: &str) -> Result<(), &'static str> {
+    match reqwest::Url::parse(url.trim()) {
+        Ok(u) if !matches!(u.scheme(), "http" | "https") => Err("use http:// ou https://"),
+        Ok(u) if u.host_str().is_none() => Err("URL sem host"),
+        Ok(_) => Ok(()),
+        Err(_) => Err("URL inválida"),
+    }
+}
+
+fn normalizar_url(url: &str) -> String {
+    url.trim().trim_end_matches('/').to_string()
+}
+
+/// Valida e normaliza uma URL digitada (ex: para testar antes de salvar).
+pub fn parse_url(url: &str) -> Result<String, AppError> {
+    validar_url(url).map_err(|m| AppError::field("url", m))?;
+    Ok(normalizar_url(url))
+}
+
+// Embutido no build. Padrão localhost:3001 (cada máquina tem app + servidor local)
+fn url_padrao() -> String {
+    option_env!("TAURI_APP_CLOUD_API_URL")
+        .map(String::from)
+        .or_else(|| std::env::var("TAURI_APP_CLOUD_API_URL").ok())
+        .unwrap_or_else(|| "http://localhost:3001".to_string())
+}
+
+fn perfis(conn: &Connection) -> Vec<PerfilServidor> {
+    db::config_value(conn, PERFIS_KEY)
+}
+
+fn ativo(conn: &Connection) -> Option<String> {
+    db::config_value(conn, ATIVO_KEY)
+}
+
+fn gravar_perfis(conn: &Connection, perfis: &[PerfilServidor]) -> Result<(), AppError> {
+    let value = serde_json::to_string(perfis).map_err(|e| AppError::Internal(e.to_string()))?;
+    db::put_config_value(conn, PERFIS_KEY, &value)
+}
+
+/// URL do servidor ativo: a do perfil escolhido ou, sem perfil, a do build.
+pub fn url_ativa(conn: &Connection) -> String {
+    let nome = ativo(conn);
+    perfis(conn)
+        .into_iter()
+        .find(|p| Some(&p.nome) == nome.as_ref())
+        .map(|p| p.url)
+        .unwrap_or_else(url_padrao)
+}
+
+pub fn list_servidores(conn: &Connection) -> Result<Servidores, AppError> {
+    Ok(Servidores { perfis: perfis(conn), ativo: ativo(conn), padrao: url_padrao(), url: url_ativa(conn) })
+}
+
+// Trocar de servidor zera o cursor do sync, para o próximo pull trazer tudo do
+// servidor novo, e põe a base inteira no outbox, porque ele não tem nada do que o
+// antigo já recebeu. Devolve se trocou: quem chama encerra a sessão (apaga o token)
+// só depois do commit, para um rollback não deixar o aparelho deslogado à toa.
+fn trocou_servidor(conn: &Connection, url_antes: &str) -> Result<bool, AppError> {
+    if url_ativa(conn) == url_antes {
+        return Ok(false);
+    }
+    conn.execute("DELETE FROM config WHERE key IN ('lastSyncedAt', ?1)", [db::CURSOR_SYNC_KEY])?;
+    db::mark_all_changed(conn)?;
+    Ok(true)
+}
+
+fn encerrar_sessao(trocou: bool) -> Result<(), AppError> {
+    if trocou {
+        credenciais::set_token(None)?;
+    }
+    Ok(())
+}
+
+/// Cria ou atualiza (pelo nome) um perfil.
+pub fn put_servidor(conn: &Connection, perfil: &PerfilServidor) -> Result<(), AppError> {
+    perfil.validate()?;
+    let perfil = PerfilServidor { nome: perfil.nome.trim().to_string(), url: normalizar_url(&perfil.url) };
+    let mut lista = perfis(conn);
+    match lista.iter_mut().find(|p| p.nome == perfil.nome) {
+        Some(p) => *p = perfil,
+        None => lista.push(perfil),
+    }
+    let url_antes = url_ativa(conn);
+    let db_tx = conn.unchecked_transaction()?;
+    gravar_perfis(&db_tx, &lista)?;
+    let trocou = trocou_servidor(&db_tx, &url_antes)?;
+    db_tx.commit()?;
+    encerrar_sessao(trocou)
+}
+
+/// Remove um perfil; se era o ativo, o sync volta para a URL do build.
+pub fn delete_servidor(conn: &Connection, nome: &str) -> Result<(), AppError> {
+    let mut lista = perfis(conn);
+    let antes = lista.len();
+    lista.retain(|p| p.nome != nome);
+    if lista.len() == antes {
+        return Err(AppError::NotFound(format!("servidor não encontrado: {}", nome)));
+    }
+    let url_antes = url_ativa(conn);
+    let db_tx = conn.unchecked_transaction()?;
+    gravar_perfis(&db_tx, &lista)?;
+    if ativo(&db_tx).as_deref() == Some(nome) {
+        db_tx.execute("DELETE FROM config WHERE key = ?1", [ATIVO_KEY])?;
+    }
+    let trocou = trocou_servidor(&db_tx, &url_antes)?;
+    db_tx.commit()?;
+    encerrar_sessao(trocou)
+}
+
+/// Ativa um perfil (`None`: volta para a URL do build), usado a partir do próximo
+/// pull, push ou restore. Mudando a URL, é preciso fazer login de novo.
+pub fn ativar_servidor(conn: &Connection, nome: Option<&str>) -> Result<(), AppError> {
+    if let Some(nome) = nome {
+        if !perfis(conn).iter().any(|p| p.nome == nome) {
+            return Err(AppError::NotFound(format!("servidor não encontrado: {}", nome)));
+        }
+    }
+    let url_antes = url_ativa(conn);
+    let value = serde_json::to_string(&nome).map_err(|e| AppError::Internal(e.to_string()))?;
+    let db_tx = conn.unchecked_transaction()?;
+    db::put_config_value(&db_tx, ATIVO_KEY, &value)?;
+    let trocou = trocou_servidor(&db_tx, &url_antes)?;
+    db_tx.commit()?;
+    encerrar_sessao(trocou)
+}
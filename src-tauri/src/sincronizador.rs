@@ -0,0 +1,254 @@
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Manager};
+
+use crate::db;
+use crate::error::AppError;
+use crate::nuvem;
+use crate::AppState;
+
+// Espera depois da última escrita local antes do push, para juntar rajadas de
+// edições (o frontend salva a lista inteira a cada mudança).
+const DEBOUNCE_PUSH: Duration = Duration::from_secs(2);
+const INTERVALO_PULL: Duration = Duration::from_secs(5 * 60);
+const BACKOFF_INICIAL: Duration = Duration::from_secs(5);
+const BACKOFF_MAXIMO: Duration = Duration::from_secs(5 * 60);
+
+enum Sinal {
+    Alterado,
+    Sincronizar,
+    Descarregar(Sender<()>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Etapa {
+    Pull,
+    Push,
+}
+
+/// Payload de `sync://started`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InicioSync {
+    pub pull: bool,
+    pub tentativa: u32,
+}
+
+/// Payload de `sync://progress`, emitido ao fim de cada etapa do ciclo.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProgressoSync {
+    pub etapa: Etapa,
+    pub recebidos: usize,
+}
+
+/// Payload de `sync://finished`. Com `recebidos > 0` o frontend recarrega os dados;
+/// `rejeitados` são itens do servidor inválidos, guardados em registros_invalidos.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FimSync {
+    pub recebidos: usize,
+    pub rejeitados: usize,
+    pub last_synced_at: Option<String>,
+}
+
+/// Payload de `sync://error`. `novaTentativaEmMs` ausente: não há retry agendado
+/// (ex: sessão expirada); o próximo sync vem da próxima escrita ou do pull periódico.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ErroSync {
+    pub code: &'static str,
+    pub message: String,
+    pub tentativa: u32,
+    pub nova_tentativa_em_ms: Option<u64>,
+}
+
+/// Serviço de sync em segundo plano: push depois de escritas locais (com debounce),
+/// pull periódico e, em falha, nova tentativa com backoff exponencial. Os comandos
+/// só avisam; o ciclo roda na thread do worker.
+pub struct Sincronizador {
+    tx: Mutex<Sender<Sinal>>,
+    rx: Mutex<Option<Receiver<Sinal>>>,
+}
+
+impl Default for Sincronizador {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Sincronizador {
+    pub fn new() -> Self {
+        let (tx, rx) = mpsc::channel();
+        Sincronizador { tx: Mutex::new(tx), rx: Mutex::new(Some(rx)) }
+    }
+
+    /// Sobe a thread do worker; chamadas seguintes não fazem nada.
+    pub fn iniciar(&self, handle: AppHandle) {
+        let Some(rx) = self.rx.lock().ok().and_then(|mut r| r.take()) else { return };
+        let worker = Worker {
+            handle,
+            rx,
+            push_em: None,
+            pull_em: Instant::now(),
+            retry_em: None,
+            falhas: 0,
+        };
+        std::thread::spawn(move || worker.rodar());
+    }
+
+    fn enviar(&self, sinal: Sinal) -> bool {
+        self.tx.lock().map(|tx| tx.send(sinal).is_ok()).unwrap_or(false)
+    }
+
+    /// Houve escrita local: agenda o push.
+    pub fn alterado(&self) {
+        self.enviar(Sinal::Alterado);
+    }
+
+    /// Pull e push agora, zerando o backoff (ex: depois do login).
+    pub fn sincronizar(&self) {
+        self.enviar(Sinal::Sincronizar);
+    }
+
+    /// Faz o push pendente e espera terminar, até `limite` (ex: ao fechar o app).
+    pub fn descarregar(&self, limite: Duration) {
+        let (tx, rx) = mpsc::channel();
+        if self.enviar(Sinal::Descarregar(tx)) {
+            let _ = rx.recv_timeout(limite);
+        }
+    }
+}
+
+// Espera antes da nova tentativa depois de `falhas` falhas seguidas: dobra a cada
+// falha, até o máximo.
+fn backoff(falhas: u32) -> Duration {
+    BACKOFF_INICIAL.saturating_mul(2u32.saturating_pow(falhas.saturating_sub(1))).min(BACKOFF_MAXIMO)
+}
+
+struct Worker {
+    handle: AppHandle,
+    rx: Receiver<Sinal>,
+    push_em: Option<Instant>,
+    pull_em: Instant,
+    // Com falha recente, o próximo ciclo (pull + push) só roda aqui
+    retry_em: Option<Instant>,
+    falhas: u32,
+}
+
+impl Worker {
+    fn proximo_prazo(&self) -> Instant {
+        match self.retry_em {
+            Some(t) => t,
+            None => self.push_em.map_or(self.pull_em, |p| p.min(self.pull_em)),
+        }
+    }
+
+    fn rodar(mut self) {
+        loop {
+            let espera = self.proximo_prazo().saturating_duration_since(Instant::now());
+            match self.rx.recv_timeout(espera) {
+                Ok(Sinal::Alterado) => {
+                    if self.retry_em.is_none() {
+                        self.push_em = Some(Instant::now() + DEBOUNCE_PUSH);
+                    }
+                }
+                Ok(Sinal::Sincronizar) => {
+                    self.falhas = 0;
+                    self.ciclo(true);
+                }
+                Ok(Sinal::Descarregar(pronto)) => {
+                    self.ciclo(false);
+                    let _ = pronto.send(());
+                }
+                Err(RecvTimeoutError::Timeout) => {
+                    let agora = Instant::now();
+                    if self.retry_em.is_some_and(|t| t <= agora) || self.pull_em <= agora {
+                        self.ciclo(true);
+                    } else if self.push_em.is_some_and(|t| t <= agora) {
+                        self.ciclo(false);
+                    }
+                }
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+        }
+    }
+
+    fn emitir<S: Serialize + Clone>(&self, evento: &str, payload: S) {
+        let _ = self.handle.emit(evento, payload);
+    }
+
+    fn ciclo(&mut self, pull: bool) {
+        // Retry depois de falha refaz o ciclo inteiro
+        let pull = pull || self.retry_em.is_some();
+        self.push_em = None;
+        if pull {
+            self.pull_em = Instant::now() + INTERVALO_PULL;
+        }
+        let Some(state) = self.handle.try_state::<AppState>() else { return };
+        // Sem servidor ou sem login não há o que sincronizar nem erro a mostrar
+        let habilitado = state.banco().and_then(|p| p.leitura()).map(|c| db::sync_habilitado(&c)).unwrap_or(false);
+        if !habilitado {
+            self.retry_em = None;
+            return;
+        }
+        self.emitir("sync://started", InicioSync { pull, tentativa: self.falhas + 1 });
+        match self.executar(&state, pull) {
+            Ok(db::ResultadoPull { recebidos, rejeitados }) => {
+                self.falhas = 0;
+                self.retry_em = None;
+                let last_synced_at = state.banco().and_then(|p| p.leitura()).ok().and_then(|c| db::get_config(&c).ok()?.last_synced_at);
+                self.emitir("sync://finished", FimSync { recebidos, rejeitados, last_synced_at });
+            }
+            Err(e) => {
+                self.falhas += 1;
+                // Token inválido não melhora com retry
+                let espera = match e {
+                    AppError::Unauthorized(_) => None,
+                    _ => Some(backoff(self.falhas)),
+                };
+                self.retry_em = espera.map(|d| Instant::now() + d);
+                eprintln!("[Sync] tentativa {} falhou: {}", self.falhas, e);
+                self.emitir(
+                    "sync://error",
+                    ErroSync {
+                        code: e.code(),
+                        message: e.to_string(),
+                        tentativa: self.falhas,
+                        nova_tentativa_em_ms: espera.map(|d| d.as_millis() as u64),
+                    },
+                );
+            }
+        }
+    }
+
+    // Pull antes do push, para o merge local acontecer antes de enviar. A conexão
+    // só é tomada nas etapas de banco; a rede roda sem lock.
+    fn executar(&self, state: &AppState, pull: bool) -> Result<db::ResultadoPull, AppError> {
+        let pool = state.banco()?;
+        let mut resultado = db::ResultadoPull::default();
+        if pull {
+            resultado = tauri::async_runtime::block_on(nuvem::sync_pull(pool, None))?;
+            self.emitir("sync://progress", ProgressoSync { etapa: Etapa::Pull, recebidos: resultado.recebidos });
+        }
+        tauri::async_runtime::block_on(nuvem::sync_push(pool))?;
+        self.emitir("sync://progress", ProgressoSync { etapa: Etapa::Push, recebidos: resultado.recebidos });
+        Ok(resultado)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backoff_dobra_ate_o_maximo() {
+        let esperas: Vec<u64> = (1..=9).map(|f| backoff(f).as_secs()).collect();
+        assert_eq!(esperas, [5, 10, 20, 40, 80, 160, 300, 300, 300]);
+        assert_eq!(backoff(u32::MAX), BACKOFF_MAXIMO);
+    }
+}
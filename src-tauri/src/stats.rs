@@ -0,0 +1,202 @@
+use rusqlite::types::Value as SqlValue;
+use rusqlite::{params_from_iter, Connection};
+use serde::Serialize;
+
+use crate::error::AppError;
+use crate::money::Money;
+use crate::query::{build_where, TransacaoFiltro};
+
+// Mesmas regras de useEstatisticas/useMetricasEmpresa: lixeira fora, valores
+// sempre positivos com o sinal dado por `type`, "pago" separa o realizado.
+const SOMAS: &str = "COALESCE(SUM(CASE WHEN type = 'entrada' THEN value END), 0),
+    COALESCE(SUM(CASE WHEN type = 'saida' THEN value END), 0),
+    COALESCE(SUM(CASE WHEN type = 'entrada' AND status = 'pago' THEN value END), 0),
+    COALESCE(SUM(CASE WHEN type = 'saida' AND status = 'pago' THEN value END), 0),
+    COUNT(*)";
+
+#[derive(Debug, Default, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Totais {
+    pub income: Money,
+    pub expense: Money,
+    pub balance: Money,
+    pub paid_income: Money,
+    pub paid_expense: Money,
+    pub pending_income: Money,
+    pub pending_expense: Money,
+    pub income_perc: f64,
+    pub expense_perc: f64,
+    pub quantidade: i64,
+}
+
+impl Totais {
+    fn from_row(row: &rusqlite::Row, offset: usize) -> rusqlite::Result<Self> {
+        let income: Money = row.get(offset)?;
+        let expense: Money = row.get(offset + 1)?;
+        let paid_income: Money = row.get(offset + 2)?;
+        let paid_expense: Money = row.get(offset + 3)?;
+        let perc = |part: Money, total: Money| {
+            if total == Money::ZERO {
+                0.0
+            } else {
+                part.centavos() as f64 / total.centavos() as f64 * 100.0
+            }
+        };
+        Ok(Totais {
+            income,
+            expense,
+            balance: income - expense,
+            paid_income,
+            paid_expense,
+            pending_income: income - paid_income,
+            pending_expense: expense - paid_expense,
+            income_perc: perc(paid_income, income),
+            expense_perc: perc(paid_expense, expense),
+            quantidade: row.get(offset + 4)?,
+        })
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResumoMensal {
+    pub mes: String,
+    #[serde(flatten)]
+    pub totais: Totais,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Agrupamento {
+    pub chave: Option<String>,
+    #[serde(flatten)]
+    pub totais: Totais,
+}
+
+/// Métricas do contexto Empresa (useMetricasEmpresa).
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MetricasEmpresa {
+    pub faturamento: Money,
+    pub custo: Money,
+    pub lucro: Money,
+    pub margem: f64,
+    pub n_clientes: i64,
+    pub ticket_medio: Money,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Estatisticas {
+    pub totais: Totais,
+    pub por_categoria: Vec<Agrupamento>,
+    pub por_conta: Vec<Agrupamento>,
+    pub por_metodo_pagamento: Vec<Agrupamento>,
+    pub por_contexto: Vec<Agrupamento>,
+    pub metricas_empresa: MetricasEmpresa,
+}
+
+fn where_sql(filtro: &TransacaoFiltro) -> Result<(String, Vec<SqlValue>), AppError> {
+    let (clauses, args) = build_where(filtro)?;
+    Ok((clauses.join(" AND "), args))
+}
+
+fn agrupar(conn: &Connection, expr: &str, filtro: &TransacaoFiltro) -> Result<Vec<Agrupamento>, AppError> {
+    let (where_clause, args) = where_sql(filtro)?;
+    let sql = format!(
+        "SELECT {expr}, {SOMAS} FROM transacoes WHERE {where_clause} GROUP BY 1 ORDER BY 1",
+    );
+    let mut stmt = conn.prepare(&sql)?;
+    let rows = stmt.query_map(params_from_iter(args.iter()), |row| {
+        Ok(Agrupamento { chave: row.get(0)?, totais: Totais::from_row(row, 1)? })
+    })?;
+    let mut out = vec![];
+    for r in rows {
+        out.push(r?);
+    }
+    Ok(out)
+}
+
+/// Entradas, saídas e saldo por mês (YYYY-MM) no período do filtro.
+pub fn resumo_mensal(conn: &Connection, filtro: &TransacaoFiltro) -> Result<Vec<ResumoMensal>, AppError> {
+    Ok(agrupar(conn, "substr(data, 1, 7)", filtro)?
+        .into_iter()
+        .map(|a| ResumoMensal { mes: a.chave.unwrap_or_default(), totais: a.totais })
+        .collect())
+}
+
+fn totais(conn: &Connection, filtro: &TransacaoFiltro) -> Result<Totais, AppError> {
+    let (where_clause, args) = where_sql(filtro)?;
+    let sql = format!("SELECT {SOMAS} FROM transacoes WHERE {where_clause}");
+    Ok(conn.query_row(&sql, params_from_iter(args.iter()), |row| Totais::from_row(row, 0))?)
+}
+
+fn metricas_empresa(conn: &Connection, filtro: &TransacaoFiltro) -> Result<MetricasEmpresa, AppError> {
+    let (where_clause, args) = where_sql(filtro)?;
+    let sql = format!(
+        "SELECT COUNT(DISTINCT NULLIF(TRIM(client), '')), COUNT(*) FROM transacoes WHERE {where_clause} AND type = 'entrada'",
+    );
+    let (n_clientes, n_entradas): (i64, i64) =
+        conn.query_row(&sql, params_from_iter(args.iter()), |r| Ok((r.get(0)?, r.get(1)?)))?;
+    let t = totais(conn, filtro)?;
+    let lucro = t.income - t.expense;
+    let margem = if t.income > Money::ZERO { lucro.centavos() as f64 / t.income.centavos() as f64 * 100.0 } else { 0.0 };
+    // Math.round do frontend: arredonda .5 para cima
+    let ticket_medio = if n_entradas > 0 {
+        Money::from_centavos((2 * t.income.centavos() + n_entradas).div_euclid(2 * n_entradas))
+    } else {
+        Money::ZERO
+    };
+    Ok(MetricasEmpresa { faturamento: t.income, custo: t.expense, lucro, margem, n_clientes, ticket_medio })
+}
+
+/// Totais e agrupamentos do dashboard. As métricas de empresa usam o mesmo
+/// filtro com contexto fixado em empresa.
+pub fn estatisticas(conn: &Connection, filtro: TransacaoFiltro) -> Result<Estatisticas, AppError> {
+    let empresa = TransacaoFiltro { contexto: Some(crate::models::Contexto::Empresa), ..filtro.clone() };
+    Ok(Estatisticas {
+        totais: totais(conn, &filtro)?,
+        por_categoria: agrupar(conn, "category", &filtro)?,
+        por_conta: agrupar(conn, "account", &filtro)?,
+        por_metodo_pagamento: agrupar(conn, "metodo_pagamento", &filtro)?,
+        por_contexto: agrupar(conn, "contexto", &filtro)?,
+        metricas_empresa: metricas_empresa(conn, &empresa)?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{self, Escrita};
+    use crate::models::Transacao;
+
+    fn inserir(conn: &Connection, id: &str, data: &str, centavos: i64, tipo: &str, status: &str, deleted: bool) {
+        let tx: Transacao = serde_json::from_value(serde_json::json!({
+            "id": id, "date": data, "value": centavos, "type": tipo, "status": status, "deleted": deleted
+        }))
+        .unwrap();
+        db::write_transacao(conn, &tx, Escrita::Replicada).unwrap();
+    }
+
+    #[test]
+    fn resumo_mensal_soma_em_centavos() {
+        let conn = Connection::open_in_memory().unwrap();
+        db::migrate(&conn).unwrap();
+        // 0,10 + 0,20 em f64 daria 0,30000000000000004
+        inserir(&conn, "t1", "2026-01-05", 10, "entrada", "pago", false);
+        inserir(&conn, "t2", "2026-01-20", 20, "entrada", "previsto", false);
+        inserir(&conn, "t3", "2026-01-31", 1999, "saida", "pago", false);
+        inserir(&conn, "t4", "2026-01-15", 50000, "saida", "pago", true);
+        inserir(&conn, "t5", "2026-02-01", 12345, "entrada", "pago", false);
+        let meses = resumo_mensal(&conn, &TransacaoFiltro::default()).unwrap();
+        assert_eq!(meses.iter().map(|m| m.mes.as_str()).collect::<Vec<_>>(), ["2026-01", "2026-02"]);
+        let jan = &meses[0].totais;
+        assert_eq!(jan.income, Money::from_centavos(30));
+        assert_eq!(jan.paid_income, Money::from_centavos(10));
+        assert_eq!(jan.pending_income, Money::from_centavos(20));
+        assert_eq!(jan.expense, Money::from_centavos(1999));
+        assert_eq!(jan.balance, Money::from_centavos(-1969));
+        assert_eq!(jan.quantidade, 3);
+        assert_eq!(meses[1].totais.income, Money::from_centavos(12345));
+    }
+}
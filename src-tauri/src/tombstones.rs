@@ -0,0 +1,172 @@
+use std::collections::HashSet;
+
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+
+use crate::db::{self, now_iso};
+use crate::error::AppError;
+use crate::models::{Entidade, Recorrencia};
+use crate::recorrencias;
+
+const DEVICE_ID_KEY: &str = "deviceId";
+
+/// Registro de uma exclusão definitiva. Vai para o servidor no push e volta aos
+/// outros aparelhos no pull; o servidor só a descarta depois que todos os
+/// aparelhos conhecidos a confirmaram (reenviando-a no push).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Tombstone {
+    pub entidade: Entidade,
+    pub id: String,
+    pub deleted_at: String,
+    pub device_id: String,
+}
+
+/// Id deste aparelho, gerado na primeira chamada e guardado em config.
+pub fn device_id(conn: &Connection) -> rusqlite::Result<String> {
+    conn.execute(
+        "INSERT OR IGNORE INTO config (key, value, updated_at) VALUES (?1, ?2, ?3)",
+        params![DEVICE_ID_KEY, uuid::Uuid::new_v4().to_string(), now_iso()],
+    )?;
+    conn.query_row("SELECT value FROM config WHERE key = ?1", [DEVICE_ID_KEY], |r| r.get(0))
+}
+
+fn registrar(conn: &Connection, t: &Tombstone, pushed: bool) -> rusqlite::Result<()> {
+    conn.execute(
+        "INSERT INTO tombstones (entidade, id, deleted_at, device_id, pushed) VALUES (?1, ?2, ?3, ?4, ?5)
+         ON CONFLICT (entidade, id) DO NOTHING",
+        params![t.entidade, t.id, t.deleted_at, t.device_id, pushed],
+    )?;
+    Ok(())
+}
+
+pub fn existe(conn: &Connection, entidade: Entidade, id: &str) -> rusqlite::Result<bool> {
+    conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM tombstones WHERE entidade = ?1 AND id = ?2)",
+        params![entidade, id],
+        |r| r.get(0),
+    )
+}
+
+// Apaga o registro; uma recorrência antes tira da agenda os lançamentos previstos dela.
+fn remover(conn: &Connection, entidade: Entidade, id: &str, updated_at: &str) -> Result<(), AppError> {
+    if entidade == Entidade::Recorrencia {
+        if let Some(r) = db::get_all_recorrentes(conn)?.into_iter().find(|r| r.id == id) {
+            recorrencias::propagar_alteracao(conn, &Recorrencia { ativo: false, ..r }, updated_at)?;
+        }
+    }
+    conn.execute(&format!("DELETE FROM {} WHERE id = ?1", entidade.tabela()), [id])?;
+    conn.execute("DELETE FROM sync_base WHERE entidade = ?1 AND id = ?2", params![entidade, id])?;
+    conn.execute("DELETE FROM conflitos WHERE entidade = ?1 AND id = ?2", params![entidade, id])?;
+    Ok(())
+}
+
+/// Exclusão definitiva (fora da Lixeira): apaga o registro e deixa a lápide.
+/// Mover para a Lixeira é só `deleted = true`, sincronizado como qualquer edição.
+pub fn excluir(conn: &Connection, entidade: Entidade, id: &str) -> Result<(), AppError> {
+    let db_tx = conn.unchecked_transaction()?;
+    apagar(&db_tx, entidade, id)?;
+    db_tx.commit()?;
+    Ok(())
+}
+
+/// Como `excluir`, dentro da transação de quem chama (ex: importação que substitui
+/// os dados locais).
+pub fn apagar(conn: &Connection, entidade: Entidade, id: &str) -> Result<(), AppError> {
+    let device_id = device_id(conn)?;
+    let updated_at = now_iso();
+    remover(conn, entidade, id, &updated_at)?;
+    let t = Tombstone { entidade, id: id.to_string(), deleted_at: updated_at, device_id };
+    registrar(conn, &t, false)?;
+    Ok(())
+}
+
+fn consultar(conn: &Connection, filtro: &str) -> Result<Vec<Tombstone>, AppError> {
+    let sql = format!("SELECT entidade, id, deleted_at, device_id FROM tombstones {} ORDER BY deleted_at", filtro);
+    let mut stmt = conn.prepare(&sql)?;
+    let rows = stmt.query_map([], |r| {
+        Ok(Tombstone { entidade: r.get(0)?, id: r.get(1)?, deleted_at: r.get(2)?, device_id: r.get(3)? })
+    })?;
+    let mut out = vec![];
+    for r in rows {
+        out.push(r?);
+    }
+    Ok(out)
+}
+
+/// Lápides ainda não enviadas: as criadas aqui propagam a exclusão e as recebidas
+/// confirmam ao servidor que este aparelho já a aplicou.
+pub fn pendentes(conn: &Connection) -> Result<Vec<Tombstone>, AppError> {
+    consultar(conn, "WHERE pushed = 0")
+}
+
+/// Deve rodar dentro da transação de quem confirma o push.
+pub fn marcar_enviadas(conn: &Connection, enviadas: &[Tombstone]) -> Result<(), AppError> {
+    for t in enviadas {
+        conn.execute("UPDATE tombstones SET pushed = 1 WHERE entidade = ?1 AND id = ?2", params![t.entidade, t.id])?;
+    }
+    Ok(())
+}
+
+/// Aplica as lápides recebidas no pull e guarda cada uma, pendente, para confirmar
+/// no próximo push. Lápides já enviadas que o servidor não lista mais foram
+/// confirmadas por todos os aparelhos e expurgadas lá, então saem daqui também.
+/// Deve rodar dentro da transação de quem aplica o payload.
+pub fn aplicar_remotas(conn: &Connection, remotas: &[Tombstone]) -> Result<(), AppError> {
+    let updated_at = now_iso();
+    for t in remotas {
+        remover(conn, t.entidade, &t.id, &updated_at)?;
+        registrar(conn, t, false)?;
+    }
+    let no_servidor: HashSet<(Entidade, &str)> = remotas.iter().map(|t| (t.entidade, t.id.as_str())).collect();
+    for t in consultar(conn, "WHERE pushed = 1")? {
+        if !no_servidor.contains(&(t.entidade, t.id.as_str())) {
+            conn.execute(
+                "DELETE FROM tombstones WHERE entidade = ?1 AND id = ?2",
+                params![t.entidade, t.id],
+            )?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::Escrita;
+    use crate::models::Transacao;
+
+    fn transacao(id: &str) -> Transacao {
+        serde_json::from_value(serde_json::json!({ "id": id, "date": "2026-03-10", "value": 100, "type": "saida" })).unwrap()
+    }
+
+    fn lapides(conn: &Connection) -> Vec<(String, bool)> {
+        let mut stmt = conn.prepare("SELECT id, pushed FROM tombstones ORDER BY id").unwrap();
+        stmt.query_map([], |r| Ok((r.get(0)?, r.get(1)?))).unwrap().collect::<Result<_, _>>().unwrap()
+    }
+
+    #[test]
+    fn lapide_enviada_sai_quando_o_servidor_para_de_listar() {
+        let conn = Connection::open_in_memory().unwrap();
+        db::migrate(&conn).unwrap();
+        for id in ["t1", "t2"] {
+            db::write_transacao(&conn, &transacao(id), Escrita::Replicada).unwrap();
+        }
+        excluir(&conn, Entidade::Transacao, "t1").unwrap();
+        let local = pendentes(&conn).unwrap();
+        marcar_enviadas(&conn, &local).unwrap();
+        let remota = Tombstone {
+            entidade: Entidade::Transacao,
+            id: "t2".to_string(),
+            deleted_at: now_iso(),
+            device_id: "outro".to_string(),
+        };
+        // O servidor ainda lista a de t1 (falta confirmação) e traz a de t2
+        aplicar_remotas(&conn, &[local[0].clone(), remota.clone()]).unwrap();
+        assert!(db::get_transacao(&conn, "t2").unwrap().is_none());
+        assert_eq!(lapides(&conn), [("t1".to_string(), true), ("t2".to_string(), false)]);
+        // Todos confirmaram t1 e o servidor a expurgou; t2 ainda não foi confirmada daqui
+        aplicar_remotas(&conn, &[remota]).unwrap();
+        assert_eq!(lapides(&conn), [("t2".to_string(), false)]);
+    }
+}
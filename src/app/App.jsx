@@ -14,7 +14,7 @@ import { useFiltros, useEstadoFiltros, useSeletorMes, useMetricasEmpresa } from
 import { METODOS_PAGAMENTO } from '../lib/constantes';
 import { nomeDoMes } from '../lib/formatadores';
 import { gerarId, addMeses } from '../lib/utils';
-import { restoreFromCloud, pushToCloud } from '../lib/sync';
+import { pushToCloud } from '../lib/sync';
 import * as auth from '../lib/auth';
 import { reaisParaCentavos, centavosParaReais } from '../lib/moeda';
 import * as db from '../lib/db';
@@ -23,7 +23,7 @@ function AppConteudo() {
   const { user } = useAuth();
   const { visualizacaoAtual, visualizacaoContexto, tituloAtual, aoMudarVisualizacao, aoMudarContexto } = useApp();
   const { mesAtual, pickerAberto, aoMudarMes, aoAbrirFecharPicker } = useSeletorMes(null);
-  const { transacoes, setTransacoes, recorrentes, setRecorrentes, categorias: CATEGORIAS, contas: LISTA_CONTAS, contasInvestimento, clientes: LISTA_CLIENTES, setClientes, statusLancamento, loading, excluirDefinitivamente, refreshFromDb, setSyncStatus, setSyncError } = useDados();
+  const { transacoes, setTransacoes, recorrentes, setRecorrentes, categorias: CATEGORIAS, contas: LISTA_CONTAS, contasInvestimento, clientes: LISTA_CLIENTES, setClientes, statusLancamento, loading, excluirDefinitivamente, excluirRecorrencia, restaurarDaNuvem, setSyncStatus, setSyncError } = useDados();
   const [termoBusca, setTermoBusca] = useState('');
   const [menuMobileAberto, setMenuMobileAberto] = useState(false);
   const [notificacoesAberto, setNotificacoesAberto] = useState(false);
@@ -362,7 +362,7 @@ function AppConteudo() {
           aoRemoverRecorrencia={async (id) => {
             try {
               const filtrado = recorrentes.filter((r) => r.id !== id);
-              await db.deleteRecorrencia(id);
+              await excluirRecorrencia(id);
               setRecorrentes(filtrado);
               if (auth.getToken()) pushToCloud({ recorrentes: filtrado }).catch(console.error);
             } catch (e) {
@@ -378,7 +378,7 @@ function AppConteudo() {
           itensExcluidos={itensExcluidos}
           aoRestaurar={(id) => setTransacoes((prev) => prev.map((t) => (t.id === id ? { ...t, deleted: false } : t)))}
           aoExcluirDefinitivamente={handleExcluirDefinitivamente}
-          aoRestaurarDaNuvem={async () => { await restoreFromCloud(); await refreshFromDb(); }}
+          aoRestaurarDaNuvem={restaurarDaNuvem}
         />
       </LayoutPrincipal>
 
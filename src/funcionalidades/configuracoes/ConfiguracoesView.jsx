@@ -14,7 +14,7 @@ function slugFromLabel(label) {
  * Configurações: sync, gerenciar categorias, contas, clientes e status de lançamento
  */
 export function ConfiguracoesView({ aoRestaurarDaNuvem }) {
-  const { categorias, contas, setCategorias, setContas, contasInvestimento, setContasInvestimento, clientes, setClientes, statusLancamento, setStatusLancamento, triggerPush, triggerPull } = useDados();
+  const { categorias, contas, setCategorias, setContas, contasInvestimento, setContasInvestimento, clientes, setClientes, statusLancamento, setStatusLancamento, triggerPush, triggerPull, previaRestauracao } = useDados();
   const [syncStatus, setSyncStatus] = useState('');
   const [erro, setErro] = useState('');
   const [novoCategoria, setNovoCategoria] = useState('');
@@ -34,13 +34,37 @@ export function ConfiguracoesView({ aoRestaurarDaNuvem }) {
   const [valorEditStatusLabel, setValorEditStatusLabel] = useState('');
   const { status: serverStatus, mensagem: serverMsg, verificar: verificarServidor, checking: serverChecking } = useServerStatus();
 
+  // Desktop: mostra o que se perde antes de substituir e oferece mesclar no lugar
+  const confirmarRestauracao = async () => {
+    setSyncStatus('Comparando com a nuvem...');
+    const previa = await previaRestauracao();
+    setSyncStatus('');
+    if (!previa) {
+      return window.confirm('Isso vai substituir os dados locais pelos da nuvem. Continuar?') ? 'substituir' : null;
+    }
+    if (previa.rejeitados.length > 0) {
+      throw new Error(`Dados da nuvem inválidos: ${previa.rejeitados.map((r) => `${r.field}: ${r.message}`).join('; ')}`);
+    }
+    const perdidos = previa.transacoes.somenteLocal.length;
+    const perdidasRec = previa.recorrentes.somenteLocal.length;
+    const alterados = previa.transacoes.diferentes.length + previa.recorrentes.diferentes.length;
+    const itensConfig = previa.config.reduce((n, d) => n + d.somenteLocal.length, 0);
+    const resumo = [
+      `Você vai perder ${perdidos} lançamento(s) e ${perdidasRec} recorrência(s) que só existem aqui.`,
+      `${alterados} registro(s) voltam à versão da nuvem e ${itensConfig} item(ns) de configuração saem das listas.`
+    ].join('\n');
+    if (window.confirm(`${resumo}\n\nSubstituir os dados locais pelos da nuvem?`)) return 'substituir';
+    return window.confirm('Mesclar os dados da nuvem com os locais, sem apagar nada?') ? 'mesclar' : null;
+  };
+
   const handleRestaurar = async () => {
-    if (!window.confirm('Isso vai substituir os dados locais pelos da nuvem. Continuar?')) return;
-    setSyncStatus('Restaurando...');
     setErro('');
     try {
-      await aoRestaurarDaNuvem?.();
-      setSyncStatus('Dados restaurados da nuvem.');
+      const modo = await confirmarRestauracao();
+      if (!modo) return;
+      setSyncStatus('Restaurando...');
+      await aoRestaurarDaNuvem?.(modo);
+      setSyncStatus(modo === 'mesclar' ? 'Dados da nuvem mesclados.' : 'Dados restaurados da nuvem.');
     } catch (e) {
       setErro(e?.message || 'Falha ao restaurar.');
       setSyncStatus('');
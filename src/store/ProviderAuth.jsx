@@ -41,6 +41,22 @@ export function ProviderAuth({ children }) {
     checkAuth();
   }, []);
 
+  // Desktop: sessão vencida (ou recusada pelo servidor) no sync do backend volta
+  // ao login sem apagar os dados locais; o pendente sai no push após o novo login.
+  useEffect(() => {
+    const listen = typeof window !== 'undefined' && window.__TAURI__?.event?.listen;
+    if (!listen) return;
+    const unlisten = listen('sync://error', ({ payload }) => {
+      if (payload?.code !== 'UNAUTHORIZED') return;
+      auth.logout();
+      setTokenState(null);
+      setUser(null);
+    });
+    return () => {
+      unlisten.then((f) => f()).catch(() => {});
+    };
+  }, []);
+
   const login = async (email, password, remember = true) => {
     const u = await auth.login(email, password, remember);
     setTokenState(auth.getToken());
@@ -1,7 +1,7 @@
 import React, { useState, useEffect, useRef, useCallback, createContext, useContext } from 'react';
 import * as db from '../lib/db';
 import * as auth from '../lib/auth';
-import { pullFromCloud, pushToCloud, registerPushOnClose, isTauri } from '../lib/sync';
+import { pullFromCloud, pushToCloud, registerPushOnClose, restoreFromCloud, isTauri } from '../lib/sync';
 import { DEFAULT_CONTAS, getContasFromConfig, validarContas } from '../lib/contas';
 import { normalizeClientes } from '../lib/clientes';
 import { reaisParaCentavos, centavosParaReais } from '../lib/moeda';
@@ -20,7 +20,7 @@ function pareceCentavos(val) {
 }
 
 /** Migra valores de reais para centavos (uma vez). Evita reconverter dados já em centavos. */
-function migrarParaCentavos(txs, recs, tauriInvoke) {
+function migrarParaCentavos(txs, recs) {
   if (typeof localStorage !== 'undefined' && localStorage.getItem(MIGRADO_KEY)) {
     return { txs, recs };
   }
@@ -33,8 +33,6 @@ function migrarParaCentavos(txs, recs, tauriInvoke) {
     valor: r.valor != null ? (pareceCentavos(r.valor) ? Number(r.valor) : reaisParaCentavos(r.valor)) : 0
   }));
   localStorage.setItem(MIGRADO_KEY, '1');
-  if (tauriInvoke && txsMig.length > 0) tauriInvoke('put_transacoes', txsMig).catch(() => {});
-  if (tauriInvoke && recsMig.length > 0) tauriInvoke('put_recorrentes', recsMig).catch(() => {});
   return { txs: txsMig, recs: recsMig };
 }
 
@@ -59,6 +57,10 @@ function getTauriInvoke() {
   return typeof window !== 'undefined' && window.__TAURI__?.core?.invoke;
 }
 
+function getTauriListen() {
+  return typeof window !== 'undefined' && window.__TAURI__?.event?.listen;
+}
+
 export function ProviderDados({ children }) {
   const [transacoes, setTransacoesState] = useState([]);
   const [recorrentes, setRecorrentesState] = useState([]);
@@ -136,6 +138,11 @@ export function ProviderDados({ children }) {
           if (!invoke) throw new Error('Tauri core not available');
           await lastPutTransacoesRef.current;
           if (cancelled) return;
+          // Banco antigo só com valores inteiros: sem a marca daqui, eles ainda estão em reais
+          const migrado = typeof localStorage !== 'undefined' && localStorage.getItem(MIGRADO_KEY);
+          await invoke('resolver_valores_legados', { emReais: !migrado });
+          if (typeof localStorage !== 'undefined') localStorage.setItem(MIGRADO_KEY, '1');
+          if (cancelled) return;
           // Se tem token, puxa da nuvem PRIMEIRO para garantir dados atuais ao entrar na conta
           if (auth.getToken()) {
             try {
@@ -146,6 +153,10 @@ export function ProviderDados({ children }) {
               console.log('[Sync] pull OK – dados sincronizados com o servidor');
             } catch (e) {
               console.warn('[Sync] pull falhou (servidor offline ou sem token):', e?.message || e);
+              if (e?.code === 'UNAUTHORIZED') {
+                setSyncStatus('error');
+                setSyncError('Sessão expirada.');
+              }
             }
           }
           const [txs, recs, config] = await Promise.all([
@@ -154,9 +165,8 @@ export function ProviderDados({ children }) {
             invoke('get_config').then((r) => r || {})
           ]);
           if (cancelled) return;
-          const { txs: txsMig, recs: recsMig } = migrarParaCentavos(txs || [], recs || [], getTauriInvoke());
-          setTransacoesState(Array.isArray(txsMig) ? txsMig : []);
-          setRecorrentesState(filtrarRecorrentesMock(Array.isArray(recsMig) ? recsMig : []));
+          setTransacoesState(Array.isArray(txs) ? txs : []);
+          setRecorrentesState(filtrarRecorrentesMock(Array.isArray(recs) ? recs : []));
           setCategorias(config?.categorias?.length ? config.categorias : DEFAULT_CATEGORIAS);
           setContas(getContasFromConfig(config || {}));
           setContasInvestimentoState(Array.isArray(config?.contasInvestimento) ? config.contasInvestimento : []);
@@ -250,7 +260,15 @@ export function ProviderDados({ children }) {
   }, []);
 
   useEffect(() => {
-    if (isTauri()) return;
+    if (isTauri()) {
+      // Desktop: o worker drena o outbox sozinho; com a rede de volta, não espera o backoff
+      const onOnline = () => {
+        const invoke = getTauriInvoke();
+        if (invoke) invoke('sync_agora').catch(() => {});
+      };
+      window.addEventListener('online', onOnline);
+      return () => window.removeEventListener('online', onOnline);
+    }
     const onOnline = () => setSyncStatus((s) => (s === 'offline' ? 'idle' : s));
     const onOffline = () => setSyncStatus('offline');
     window.addEventListener('online', onOnline);
@@ -330,13 +348,26 @@ export function ProviderDados({ children }) {
     setTransacoesState((prev) => prev.filter((t) => t.id !== id));
   }, []);
 
+  const excluirRecorrencia = useCallback(async (id) => {
+    if (isTauri()) {
+      const invoke = getTauriInvoke();
+      if (invoke) await invoke('delete_recorrencia', { id });
+    } else {
+      await db.deleteRecorrencia(id);
+    }
+    setRecorrentesState((prev) => prev.filter((r) => r.id !== id));
+  }, []);
+
   const refreshFromDb = useCallback(async () => {
-    const [txs, recs, config] = await Promise.all([
-      db.getAllTransacoes(true),
-      db.getAllRecorrentes(),
-      db.getConfig()
-    ]);
-    const { txs: txsMig, recs: recsMig } = migrarParaCentavos(txs, recs);
+    const invoke = isTauri() ? getTauriInvoke() : null;
+    const [txs, recs, config] = await Promise.all(invoke
+      ? [
+        invoke('get_transacoes').then((r) => r || []),
+        invoke('get_recorrentes').then((r) => r || []),
+        invoke('get_config').then((r) => r || {})
+      ]
+      : [db.getAllTransacoes(true), db.getAllRecorrentes(), db.getConfig()]);
+    const { txs: txsMig, recs: recsMig } = invoke ? { txs, recs } : migrarParaCentavos(txs, recs);
     setTransacoesState(txsMig);
     setRecorrentesState(filtrarRecorrentesMock(recsMig));
     if (config.categorias?.length) setCategorias(config.categorias);
@@ -346,6 +377,43 @@ export function ProviderDados({ children }) {
     if ((config.statusLancamento ?? []).length) setStatusLancamentoState(config.statusLancamento);
   }, []);
 
+  // Desktop: o sync roda no backend; os eventos sync:// mantêm o status na tela
+  useEffect(() => {
+    const listen = isTauri() ? getTauriListen() : null;
+    if (!listen) return;
+    const unlisteners = [
+      listen('sync://started', () => {
+        setSyncStatus('syncing');
+        setSyncError(null);
+      }),
+      listen('sync://finished', ({ payload }) => {
+        setSyncStatus('synced');
+        if (payload?.lastSyncedAt) setLastSyncedAt(payload.lastSyncedAt);
+        if (payload?.recebidos > 0) refreshFromDb().catch(() => {});
+        if (payload?.rejeitados > 0) setSyncError(`${payload.rejeitados} registro(s) da nuvem com dados inválidos não foram aplicados.`);
+      }),
+      listen('sync://error', ({ payload }) => {
+        setSyncStatus(payload?.code === 'NETWORK' ? 'offline' : 'error');
+        setSyncError(payload?.code === 'UNAUTHORIZED' ? 'Sessão expirada.' : payload?.message || 'Falha ao sincronizar.');
+      })
+    ];
+    return () => unlisteners.forEach((p) => p.then((unlisten) => unlisten()).catch(() => {}));
+  }, [refreshFromDb]);
+
+  /** Desktop: o que o restore mudaria (somenteLocal = o que se perde). No PWA não há prévia. */
+  const previaRestauracao = useCallback(async () => {
+    const invoke = isTauri() ? getTauriInvoke() : null;
+    return invoke ? invoke('preview_restore') : null;
+  }, []);
+
+  /** Restaura da nuvem; modo 'substituir' (padrão) ou 'mesclar' (só no desktop). */
+  const restaurarDaNuvem = useCallback(async (modo = 'substituir') => {
+    const invoke = isTauri() ? getTauriInvoke() : null;
+    if (invoke) await invoke('restore_from_cloud', { modo });
+    else await restoreFromCloud();
+    await refreshFromDb();
+  }, [refreshFromDb]);
+
   const value = {
     transacoes,
     setTransacoes,
@@ -407,7 +475,10 @@ export function ProviderDados({ children }) {
     },
     loading,
     excluirDefinitivamente,
+    excluirRecorrencia,
     refreshFromDb,
+    previaRestauracao,
+    restaurarDaNuvem,
     syncStatus,
     lastSyncedAt,
     syncError,